@@ -0,0 +1,128 @@
+use handlebars::Handlebars;
+use serde::Deserialize;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::color::Color;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("failed to render `{template}`")]
+    Render {
+        template: String,
+        #[source]
+        source: Box<handlebars::RenderError>,
+    },
+
+    #[error("`{template}` is not a color or the name of one, got `{value}`")]
+    InvalidColor { template: String, value: String },
+}
+
+/// One entry of a `whiskers.contrast` frontmatter block: a foreground and
+/// background that must have a WCAG 2 contrast ratio of at least `min`.
+///
+/// `fg` and `bg` are rendered as templates against each flavor's context, and
+/// may give a color directly, as in `"{{darken surface0 0.1}}"`, or name a
+/// context variable such as `text` or `colors.red.hex`.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ContrastPair {
+    pub fg: String,
+    pub bg: String,
+    #[serde(default = "default_min")]
+    pub min: f64,
+}
+
+/// WCAG AA for normal text.
+const fn default_min() -> f64 {
+    4.5
+}
+
+/// The colors a [`ContrastPair`] resolved to, as hex, and their contrast ratio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub fg: String,
+    pub bg: String,
+    pub ratio: f64,
+}
+
+impl ContrastPair {
+    /// Resolve the pair's colors against `ctx` and measure their contrast.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `fg` or `bg` fails to render, or isn't a color.
+    pub fn measure(&self, reg: &Handlebars, ctx: &Value) -> Result<Measurement, Error> {
+        let fg = resolve(&self.fg, reg, ctx)?;
+        let bg = resolve(&self.bg, reg, ctx)?;
+        Ok(Measurement {
+            fg: fg.to_hex(),
+            bg: bg.to_hex(),
+            ratio: fg.contrast(bg),
+        })
+    }
+}
+
+fn resolve(template: &str, reg: &Handlebars, ctx: &Value) -> Result<Color, Error> {
+    let value = reg
+        .render_template(template, ctx)
+        .map_err(|source| Error::Render {
+            template: template.to_string(),
+            source: Box::new(source),
+        })?;
+    let value = value.trim();
+
+    crate::parse::parse_color(value)
+        .ok()
+        .or_else(|| {
+            let pointer = format!("/{}", value.replace('.', "/"));
+            crate::parse::parse_color(ctx.pointer(&pointer)?.as_str()?).ok()
+        })
+        .ok_or_else(|| Error::InvalidColor {
+            template: template.to_string(),
+            value: value.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::{make_context, make_registry, FLAVORS};
+
+    fn measure(fg: &str, bg: &str) -> Result<Measurement, Error> {
+        let pair = ContrastPair {
+            fg: fg.to_string(),
+            bg: bg.to_string(),
+            min: default_min(),
+        };
+        let ctx = make_context(&FLAVORS, Some(&FLAVORS[3]));
+        pair.measure(&make_registry(), &ctx)
+    }
+
+    #[test]
+    fn resolves_colors() {
+        let by_name = measure("text", "base").expect("valid pair");
+        assert_eq!(
+            (by_name.fg.as_str(), by_name.bg.as_str()),
+            ("cdd6f4", "1e1e2e")
+        );
+        assert!((by_name.ratio - 11.34).abs() < 0.01);
+
+        let by_template = measure("{{text}}", "colors.base.hex").expect("valid pair");
+        assert_eq!(by_template, by_name);
+
+        let derived = measure("#fff", "{{darken base 1}}").expect("valid pair");
+        assert!((derived.ratio - 21.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn invalid_colors() {
+        assert!(matches!(
+            measure("nope", "base"),
+            Err(Error::InvalidColor { .. })
+        ));
+        assert!(matches!(
+            measure("{{nope}}", "base"),
+            Err(Error::Render { .. })
+        ));
+    }
+}
@@ -0,0 +1,770 @@
+use std::str::FromStr;
+
+use crate::parse::{rgba_from_hex, Error};
+
+/// Linear sRGB to LMS cone responses, from <https://bottosson.github.io/posts/oklab/>.
+const LINEAR_TO_LMS: [[f64; 3]; 3] = [
+    [0.412_221_470_8, 0.536_332_536_3, 0.051_445_992_9],
+    [0.211_903_498_2, 0.680_699_545_1, 0.107_396_956_6],
+    [0.088_302_461_9, 0.281_718_837_6, 0.629_978_700_5],
+];
+
+const LMS_TO_OKLAB: [[f64; 3]; 3] = [
+    [0.210_454_255_3, 0.793_617_785_0, -0.004_072_046_8],
+    [1.977_998_495_1, -2.428_592_205_0, 0.450_593_709_9],
+    [0.025_904_037_1, 0.782_771_766_2, -0.808_675_766_0],
+];
+
+const OKLAB_TO_LMS: [[f64; 3]; 3] = [
+    [1.0, 0.396_337_777_4, 0.215_803_757_3],
+    [1.0, -0.105_561_345_8, -0.063_854_172_8],
+    [1.0, -0.089_484_177_5, -1.291_485_548_0],
+];
+
+const LMS_TO_LINEAR: [[f64; 3]; 3] = [
+    [4.076_741_662_1, -3.307_711_591_3, 0.230_969_929_2],
+    [-1.268_438_004_6, 2.609_757_401_1, -0.341_319_396_5],
+    [-0.004_196_086_3, -0.703_418_614_7, 1.707_614_701_0],
+];
+
+/// Channels this far outside of 0 to 1 round to the same 8-bit value as the
+/// clipped channel, so the color is treated as in gamut.
+const GAMUT_EPSILON: f64 = 0.5 / 255.0;
+
+/// The OKLCH lightness of the lightest and darkest shades of
+/// [`Color::scale`], matching the 50 and 950 shades of Tailwind CSS.
+const SCALE_LIGHTNESS: (f64, f64) = (0.97, 0.26);
+
+/// Below this OKLCH chroma a color is gray and its hue is meaningless.
+const ACHROMATIC: f64 = 1e-4;
+
+fn transform(matrix: [[f64; 3]; 3], [x, y, z]: [f64; 3]) -> [f64; 3] {
+    matrix.map(|[m0, m1, m2]| m0.mul_add(x, m1.mul_add(y, m2 * z)))
+}
+
+/// The color space that [`Color::mix`] interpolates in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Space {
+    /// Gamma encoded sRGB, as Sass and most tools mix.
+    #[default]
+    Srgb,
+    /// Linear light sRGB, which mixes like light does.
+    Linear,
+    /// Oklab, which keeps perceived lightness even.
+    Oklab,
+    /// OKLCH, which also keeps chroma and takes the shorter way around the
+    /// hue circle.
+    Oklch,
+}
+
+impl FromStr for Space {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "srgb" => Ok(Self::Srgb),
+            "linear" => Ok(Self::Linear),
+            "oklab" => Ok(Self::Oklab),
+            "oklch" => Ok(Self::Oklch),
+            _ => Err(format!(
+                "unknown color space `{s}`, expected srgb, linear, oklab, or oklch"
+            )),
+        }
+    }
+}
+
+/// An sRGB color with floating point components from 0 to 1.
+///
+/// Adjustments are made on the floats and only rounded when the color is
+/// written out, so no-op adjustments return the input exactly and chains of
+/// adjustments don't drift.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+    pub a: f64,
+}
+
+impl Color {
+    #[must_use]
+    pub const fn new(r: f64, g: f64, b: f64, a: f64) -> Self {
+        Self { r, g, b, a }
+    }
+
+    #[must_use]
+    pub fn from_rgba8([r, g, b, a]: [u8; 4]) -> Self {
+        Self::new(
+            f64::from(r) / 255.0,
+            f64::from(g) / 255.0,
+            f64::from(b) / 255.0,
+            f64::from(a) / 255.0,
+        )
+    }
+
+    /// Parse a hex color in any of the forms accepted by
+    /// [`normalize_hex`](crate::parse::normalize_hex).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `hex` isn't a hex color.
+    pub fn from_hex(hex: &str) -> Result<Self, Error> {
+        rgba_from_hex(hex).map(Self::from_rgba8)
+    }
+
+    /// Build a color from a hue in degrees, and saturation and lightness
+    /// from 0 to 1.
+    #[must_use]
+    pub fn from_hsl(hue: f64, saturation: f64, lightness: f64, alpha: f64) -> Self {
+        let (saturation, lightness) = (saturation.clamp(0.0, 1.0), lightness.clamp(0.0, 1.0));
+        let chroma = (1.0 - 2.0f64.mul_add(lightness, -1.0).abs()) * saturation;
+        let channel = |n: f64| {
+            let k = (n + hue.rem_euclid(360.0) / 30.0) % 12.0;
+            (chroma / 2.0).mul_add(-(k - 3.0).min(9.0 - k).clamp(-1.0, 1.0), lightness)
+        };
+        Self::new(channel(0.0), channel(8.0), channel(4.0), alpha)
+    }
+
+    /// Build a color from linear light sRGB components.
+    #[must_use]
+    pub fn from_linear(linear: [f64; 3], alpha: f64) -> Self {
+        let [r, g, b] = linear.map(|c| {
+            if c <= 0.003_130_8 {
+                12.92 * c
+            } else {
+                1.055f64.mul_add(c.powf(1.0 / 2.4), -0.055)
+            }
+        });
+        Self::new(r, g, b, alpha)
+    }
+
+    /// The red, green, and blue components in linear light.
+    #[must_use]
+    pub fn to_linear(self) -> [f64; 3] {
+        [self.r, self.g, self.b].map(|c| {
+            if c <= 0.040_45 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        })
+    }
+
+    /// Build a color from Oklab lightness and `a` and `b` axes. The result
+    /// may be out of the sRGB gamut; see [`Color::clip`].
+    #[must_use]
+    pub fn from_oklab(lightness: f64, a_axis: f64, b_axis: f64, alpha: f64) -> Self {
+        let lms = transform(OKLAB_TO_LMS, [lightness, a_axis, b_axis]).map(|c| c.powi(3));
+        Self::from_linear(transform(LMS_TO_LINEAR, lms), alpha)
+    }
+
+    /// The Oklab lightness from 0 to 1, and the `a` and `b` axes.
+    #[must_use]
+    pub fn to_oklab(self) -> (f64, f64, f64) {
+        let lms = transform(LINEAR_TO_LMS, self.to_linear()).map(f64::cbrt);
+        transform(LMS_TO_OKLAB, lms).into()
+    }
+
+    /// Build a color from OKLCH lightness from 0 to 1, chroma, and hue in
+    /// degrees. Colors out of the sRGB gamut lose chroma until they fit,
+    /// keeping their lightness and hue.
+    #[must_use]
+    pub fn from_oklch(lightness: f64, chroma: f64, hue: f64, alpha: f64) -> Self {
+        let lightness = lightness.clamp(0.0, 1.0);
+        let (sin, cos) = hue.to_radians().sin_cos();
+        let make = |chroma: f64| Self::from_oklab(lightness, chroma * cos, chroma * sin, alpha);
+
+        let color = make(chroma.max(0.0));
+        if color.in_gamut() {
+            return color.clip();
+        }
+        let (mut low, mut high) = (0.0, chroma);
+        for _ in 0..32 {
+            let mid = f64::midpoint(low, high);
+            if make(mid).in_gamut() {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        make(low).clip()
+    }
+
+    /// The OKLCH lightness from 0 to 1, chroma, and hue in degrees.
+    #[must_use]
+    pub fn to_oklch(self) -> (f64, f64, f64) {
+        let (lightness, a_axis, b_axis) = self.to_oklab();
+        let chroma = a_axis.hypot(b_axis);
+        let hue = if chroma < ACHROMATIC {
+            0.0
+        } else {
+            b_axis.atan2(a_axis).to_degrees().rem_euclid(360.0)
+        };
+        (lightness, chroma, hue)
+    }
+
+    /// Whether the color can be shown in sRGB, give or take rounding.
+    #[must_use]
+    pub fn in_gamut(self) -> bool {
+        [self.r, self.g, self.b]
+            .iter()
+            .all(|c| (-GAMUT_EPSILON..=1.0 + GAMUT_EPSILON).contains(c))
+    }
+
+    /// Clip the red, green, and blue components to the sRGB gamut.
+    #[must_use]
+    pub fn clip(self) -> Self {
+        let [r, g, b] = [self.r, self.g, self.b].map(|c| c.clamp(0.0, 1.0));
+        Self::new(r, g, b, self.a)
+    }
+
+    /// The hue in degrees, and the saturation and lightness from 0 to 1.
+    #[must_use]
+    #[allow(clippy::float_cmp)] // `max` is exactly one of the channels
+    pub fn to_hsl(self) -> (f64, f64, f64) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let lightness = f64::midpoint(max, min);
+        let chroma = max - min;
+        if chroma == 0.0 {
+            return (0.0, 0.0, lightness);
+        }
+
+        let saturation = chroma / (1.0 - 2.0f64.mul_add(lightness, -1.0).abs());
+        let hue = if max == self.r {
+            ((self.g - self.b) / chroma).rem_euclid(6.0)
+        } else if max == self.g {
+            (self.b - self.r) / chroma + 2.0
+        } else {
+            (self.r - self.g) / chroma + 4.0
+        };
+        (hue * 60.0, saturation, lightness)
+    }
+
+    /// The components from 0 to 255, rounded to the nearest integer.
+    #[must_use]
+    #[allow(clippy::cast_sign_loss)] // clamped to 0..=255 first
+    pub fn to_rgba8(self) -> [u8; 4] {
+        [self.r, self.g, self.b, self.a].map(|c| (c * 255.0).round().clamp(0.0, 255.0) as u8)
+    }
+
+    /// Lowercase hex, with an alpha channel only if the color is translucent.
+    #[must_use]
+    pub fn to_hex(self) -> String {
+        match self.to_rgba8() {
+            [r, g, b, 255] => format!("{r:02x}{g:02x}{b:02x}"),
+            [r, g, b, a] => format!("{r:02x}{g:02x}{b:02x}{a:02x}"),
+        }
+    }
+
+    /// Add `amount` to the HSL lightness.
+    #[must_use]
+    pub fn lighten(self, amount: f64) -> Self {
+        let (h, s, l) = self.to_hsl();
+        Self::from_hsl(h, s, l + amount, self.a)
+    }
+
+    /// Subtract `amount` from the HSL lightness.
+    #[must_use]
+    pub fn darken(self, amount: f64) -> Self {
+        self.lighten(-amount)
+    }
+
+    /// Add `amount` to the HSL saturation.
+    #[must_use]
+    pub fn saturate(self, amount: f64) -> Self {
+        let (h, s, l) = self.to_hsl();
+        Self::from_hsl(h, s + amount, l, self.a)
+    }
+
+    /// Subtract `amount` from the HSL saturation.
+    #[must_use]
+    pub fn desaturate(self, amount: f64) -> Self {
+        self.saturate(-amount)
+    }
+
+    /// The same color with an HSL lightness of `lightness`, from 0 to 1.
+    #[must_use]
+    pub fn set_lightness(self, lightness: f64) -> Self {
+        let (h, s, _) = self.to_hsl();
+        Self::from_hsl(h, s, lightness, self.a)
+    }
+
+    /// The same color with an HSL hue of `hue` degrees.
+    #[must_use]
+    pub fn set_hue(self, hue: f64) -> Self {
+        let (_, s, l) = self.to_hsl();
+        Self::from_hsl(hue, s, l, self.a)
+    }
+
+    /// The color opposite on the HSL hue wheel.
+    #[must_use]
+    pub fn complement(self) -> Self {
+        let (h, s, l) = self.to_hsl();
+        Self::from_hsl(h + 180.0, s, l, self.a)
+    }
+
+    /// The gray with the same HSL lightness, as in Sass.
+    #[must_use]
+    pub fn grayscale(self) -> Self {
+        let (h, _, l) = self.to_hsl();
+        Self::from_hsl(h, 0.0, l, self.a)
+    }
+
+    /// Invert the red, green, and blue components, keeping the alpha channel.
+    #[must_use]
+    pub fn invert(self) -> Self {
+        Self::new(1.0 - self.r, 1.0 - self.g, 1.0 - self.b, self.a)
+    }
+
+    /// Add `amount` to the OKLCH lightness, which keeps the perceived hue
+    /// and colorfulness unlike [`Color::lighten`].
+    #[must_use]
+    pub fn lighten_ok(self, amount: f64) -> Self {
+        let (lightness, chroma, hue) = self.to_oklch();
+        Self::from_oklch(lightness + amount, chroma, hue, self.a)
+    }
+
+    /// Subtract `amount` from the OKLCH lightness.
+    #[must_use]
+    pub fn darken_ok(self, amount: f64) -> Self {
+        self.lighten_ok(-amount)
+    }
+
+    /// Add `amount` to the OKLCH chroma. Negative amounts move towards gray.
+    #[must_use]
+    pub fn chroma(self, amount: f64) -> Self {
+        let (lightness, chroma, hue) = self.to_oklch();
+        Self::from_oklch(lightness, chroma + amount, hue, self.a)
+    }
+
+    /// Rotate the OKLCH hue by `degrees`.
+    #[must_use]
+    pub fn hue_rotate(self, degrees: f64) -> Self {
+        let (lightness, chroma, hue) = self.to_oklch();
+        Self::from_oklch(lightness, chroma, hue + degrees, self.a)
+    }
+
+    /// Mix with `other` in `space`, taking `weight` of this color and the
+    /// rest of `other`. Translucent colors contribute less, as in Sass.
+    #[must_use]
+    pub fn mix(self, other: Self, weight: f64, space: Space) -> Self {
+        let w = weight.mul_add(2.0, -1.0);
+        let a = self.a - other.a;
+        let rgb_weight = if (w * a + 1.0).abs() < f64::EPSILON {
+            w
+        } else {
+            (w + a) / w.mul_add(a, 1.0)
+        };
+        let rgb_weight = f64::midpoint(rgb_weight, 1.0);
+
+        let channel = |lhs: f64, rhs: f64| lhs.mul_add(rgb_weight, rhs * (1.0 - rgb_weight));
+        let alpha = self.a.mul_add(weight, other.a * (1.0 - weight));
+
+        match space {
+            Space::Srgb => Self::new(
+                channel(self.r, other.r),
+                channel(self.g, other.g),
+                channel(self.b, other.b),
+                alpha,
+            ),
+            Space::Linear => {
+                let (lhs, rhs) = (self.to_linear(), other.to_linear());
+                Self::from_linear([0, 1, 2].map(|i| channel(lhs[i], rhs[i])), alpha)
+            }
+            Space::Oklab => {
+                let (lhs, rhs) = (self.to_oklab(), other.to_oklab());
+                Self::from_oklab(
+                    channel(lhs.0, rhs.0),
+                    channel(lhs.1, rhs.1),
+                    channel(lhs.2, rhs.2),
+                    alpha,
+                )
+                .clip()
+            }
+            Space::Oklch => {
+                let (lhs, rhs) = (self.to_oklch(), other.to_oklch());
+                // a gray has no hue of its own, so it takes the other color's
+                let (lhs_hue, rhs_hue) = match (lhs.1 < ACHROMATIC, rhs.1 < ACHROMATIC) {
+                    (true, false) => (rhs.2, rhs.2),
+                    (false, true) => (lhs.2, lhs.2),
+                    _ => (lhs.2, rhs.2),
+                };
+                let difference = (lhs_hue - rhs_hue + 180.0).rem_euclid(360.0) - 180.0;
+                Self::from_oklch(
+                    channel(lhs.0, rhs.0),
+                    channel(lhs.1, rhs.1),
+                    difference.mul_add(rgb_weight, rhs_hue),
+                    alpha,
+                )
+            }
+        }
+    }
+
+    /// `steps` colors evenly spaced from this color to `other` in `space`,
+    /// including both ends.
+    #[must_use]
+    pub fn gradient(self, other: Self, steps: u32, space: Space) -> Vec<Self> {
+        if steps == 1 {
+            return vec![self];
+        }
+        (0..steps)
+            .map(|step| {
+                let t = f64::from(step) / f64::from(steps - 1);
+                self.mix(other, 1.0 - t, space)
+            })
+            .collect()
+    }
+
+    /// `steps` shades of this color from light to dark, evenly spaced in
+    /// OKLCH lightness and keeping the hue and as much chroma as sRGB allows.
+    #[must_use]
+    pub fn scale(self, steps: u32) -> Vec<Self> {
+        let (_, chroma, hue) = self.to_oklch();
+        let (lightest, darkest) = SCALE_LIGHTNESS;
+        let lightness = |step: u32| match steps {
+            1 => f64::midpoint(lightest, darkest),
+            _ => (darkest - lightest).mul_add(f64::from(step) / f64::from(steps - 1), lightest),
+        };
+        (0..steps)
+            .map(|step| Self::from_oklch(lightness(step), chroma, hue, self.a))
+            .collect()
+    }
+
+    /// The same color with the alpha channel set to `alpha`.
+    #[must_use]
+    pub const fn with_alpha(self, alpha: f64) -> Self {
+        Self {
+            a: alpha.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    /// This color drawn over an opaque `background`.
+    #[must_use]
+    pub fn over(self, background: Self) -> Self {
+        let channel = |fg: f64, bg: f64| fg.mul_add(self.a, bg * (1.0 - self.a));
+        Self::new(
+            channel(self.r, background.r),
+            channel(self.g, background.g),
+            channel(self.b, background.b),
+            1.0,
+        )
+    }
+
+    /// The WCAG 2 relative luminance, from 0 for black to 1 for white.
+    #[must_use]
+    pub fn luminance(self) -> f64 {
+        let [r, g, b] = self.to_linear();
+        0.0722f64.mul_add(b, 0.2126f64.mul_add(r, 0.7152 * g))
+    }
+
+    /// The WCAG 2 contrast ratio between this color as text and
+    /// `background`, from 1 to 21. Translucent text is drawn over the
+    /// background first.
+    #[must_use]
+    pub fn contrast(self, background: Self) -> f64 {
+        let text = self.over(background).luminance();
+        let background = background.luminance();
+        (text.max(background) + 0.05) / (text.min(background) + 0.05)
+    }
+
+    /// The APCA lightness contrast (Lc) of this color as text on
+    /// `background`, from about 106 for black on white to about -108 for
+    /// white on black. Translucent text is drawn over the background first.
+    ///
+    /// Uses the constants of APCA-W3 0.0.98G, see
+    /// <https://github.com/Myndex/apca-w3>.
+    #[must_use]
+    pub fn apca(self, background: Self) -> f64 {
+        let screen_luminance = |color: Self| {
+            let [r, g, b] = [color.r, color.g, color.b].map(|c| c.powf(2.4));
+            let y = 0.072_175f64.mul_add(b, 0.212_672_9f64.mul_add(r, 0.715_152_2 * g));
+            // soft clamp near black, where screens flare
+            if y < 0.022 {
+                y + (0.022 - y).powf(1.414)
+            } else {
+                y
+            }
+        };
+        let text = screen_luminance(self.over(background));
+        let background = screen_luminance(background);
+        if (background - text).abs() < 0.0005 {
+            return 0.0;
+        }
+
+        let contrast = if background > text {
+            let sapc = (background.powf(0.56) - text.powf(0.57)) * 1.14;
+            if sapc < 0.1 {
+                0.0
+            } else {
+                sapc - 0.027
+            }
+        } else {
+            let sapc = (background.powf(0.65) - text.powf(0.62)) * 1.14;
+            if sapc > -0.1 {
+                0.0
+            } else {
+                sapc + 0.027
+            }
+        };
+        contrast * 100.0
+    }
+
+    /// CSS `oklab()` notation, such as `oklab(75.6% 0.130 0.006)`, with the
+    /// alpha channel after a slash if the color is translucent.
+    #[must_use]
+    pub fn to_css_oklab(self) -> String {
+        let (lightness, a_axis, b_axis) = self.to_oklab();
+        format!(
+            "oklab({:.1}% {a_axis:.3} {b_axis:.3}{})",
+            lightness * 100.0,
+            self.css_alpha()
+        )
+    }
+
+    /// CSS `oklch()` notation, such as `oklch(75.6% 0.130 2.8)`, with the
+    /// alpha channel after a slash if the color is translucent.
+    #[must_use]
+    pub fn to_css_oklch(self) -> String {
+        let (lightness, chroma, hue) = self.to_oklch();
+        format!(
+            "oklch({:.1}% {chroma:.3} {hue:.1}{})",
+            lightness * 100.0,
+            self.css_alpha()
+        )
+    }
+
+    fn css_alpha(self) -> String {
+        if self.a >= 1.0 {
+            String::new()
+        } else {
+            format!(" / {:.2}", self.a)
+        }
+    }
+
+    /// CSS `rgb()` notation, such as `rgb(243, 139, 168)`.
+    #[must_use]
+    pub fn to_css_rgb(self) -> String {
+        let [r, g, b, _] = self.to_rgba8();
+        format!("rgb({r}, {g}, {b})")
+    }
+
+    /// CSS `rgba()` notation, such as `rgba(243, 139, 168, 0.60)`.
+    #[must_use]
+    pub fn to_css_rgba(self) -> String {
+        let [r, g, b, _] = self.to_rgba8();
+        format!("rgba({r}, {g}, {b}, {:.2})", self.a)
+    }
+
+    /// CSS `hsl()` notation, such as `hsl(343, 81%, 75%)`.
+    #[must_use]
+    pub fn to_css_hsl(self) -> String {
+        let (h, s, l) = self.to_hsl();
+        format!("hsl({:.0}, {:.0}%, {:.0}%)", h, s * 100.0, l * 100.0)
+    }
+
+    /// CSS `hsla()` notation, such as `hsla(343, 81%, 75%, 0.60)`.
+    #[must_use]
+    pub fn to_css_hsla(self) -> String {
+        let (h, s, l) = self.to_hsl();
+        format!(
+            "hsla({:.0}, {:.0}%, {:.0}%, {:.2})",
+            h,
+            s * 100.0,
+            l * 100.0,
+            self.a
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(hex: &str) -> Color {
+        Color::from_hex(hex).expect("valid hex")
+    }
+
+    #[test]
+    fn no_op_adjustments_are_lossless() {
+        for hex in ["f38ba8", "ff0000", "1e1e2e", "eff1f5", "cba6f780", "000000"] {
+            assert_eq!(color(hex).lighten(0.0).to_hex(), hex);
+            assert_eq!(color(hex).darken(0.0).to_hex(), hex);
+            assert_eq!(
+                color(hex).mix(color("ffffff"), 1.0, Space::Srgb).to_hex(),
+                hex
+            );
+        }
+    }
+
+    #[test]
+    fn adjustments_do_not_drift() {
+        let red = color("f38ba8");
+        let mut adjusted = red;
+        for _ in 0..100 {
+            adjusted = adjusted.lighten(0.01).darken(0.01);
+        }
+        assert_eq!(adjusted.to_hex(), red.to_hex());
+    }
+
+    #[test]
+    fn hsl() {
+        let (h, s, l) = color("f38ba8").to_hsl();
+        assert_eq!(
+            (h.round(), (s * 100.0).round(), (l * 100.0).round()),
+            (343.0, 81.0, 75.0)
+        );
+        assert_eq!(Color::from_hsl(120.0, 1.0, 0.5, 1.0).to_hex(), "00ff00");
+        assert_eq!(color("f38ba8").to_css_hsl(), "hsl(343, 81%, 75%)");
+    }
+
+    #[test]
+    fn css() {
+        let red = color("f38ba8");
+        assert_eq!(red.to_css_rgb(), "rgb(243, 139, 168)");
+        assert_eq!(
+            red.with_alpha(0.6).to_css_rgba(),
+            "rgba(243, 139, 168, 0.60)"
+        );
+        assert_eq!(
+            red.with_alpha(0.6).to_css_hsla(),
+            "hsla(343, 81%, 75%, 0.60)"
+        );
+    }
+
+    #[test]
+    fn mix() {
+        assert_eq!(
+            color("ff0000")
+                .mix(color("0000ff"), 0.5, Space::Srgb)
+                .to_hex(),
+            "800080"
+        );
+        assert_eq!(
+            color("f38ba8")
+                .mix(color("1e1e2e"), 0.3, Space::Srgb)
+                .to_hex(),
+            "5e3f53"
+        );
+    }
+
+    #[test]
+    fn oklab() {
+        let (lightness, a_axis, b_axis) = color("ff0000").to_oklab();
+        assert!((lightness - 0.628).abs() < 1e-3);
+        assert!((a_axis - 0.2249).abs() < 1e-3);
+        assert!((b_axis - 0.1258).abs() < 1e-3);
+        for hex in ["f38ba8", "1e1e2e", "ffffff", "000000", "cba6f780"] {
+            let (lightness, a_axis, b_axis) = color(hex).to_oklab();
+            let alpha = color(hex).a;
+            assert_eq!(
+                Color::from_oklab(lightness, a_axis, b_axis, alpha).to_hex(),
+                hex
+            );
+            assert_eq!(color(hex).lighten_ok(0.0).to_hex(), hex);
+            assert_eq!(color(hex).hue_rotate(0.0).to_hex(), hex);
+            assert_eq!(color(hex).chroma(0.0).to_hex(), hex);
+        }
+        assert_eq!(color("f38ba8").to_css_oklch(), "oklch(75.6% 0.130 2.8)");
+    }
+
+    #[test]
+    fn oklch_gamut() {
+        // far more chroma than sRGB can show: hue and lightness are kept
+        let vivid = Color::from_oklch(0.7, 0.4, 150.0, 1.0);
+        assert!(vivid.in_gamut());
+        let (lightness, chroma, hue) = vivid.to_oklch();
+        assert!((lightness - 0.7).abs() < 1e-3);
+        assert!((hue - 150.0).abs() < 1.0);
+        assert!(chroma < 0.4);
+    }
+
+    #[test]
+    fn mix_spaces() {
+        let (black, white) = (color("000000"), color("ffffff"));
+        assert_eq!(black.mix(white, 0.5, Space::Srgb).to_hex(), "808080");
+        assert_eq!(black.mix(white, 0.5, Space::Linear).to_hex(), "bcbcbc");
+        assert_eq!(black.mix(white, 0.5, Space::Oklab).to_hex(), "636363");
+        assert_eq!(black.mix(white, 0.5, Space::Oklch).to_hex(), "636363");
+        // the gray takes red's hue, rather than swinging through 0 degrees
+        let (_, _, hue) = color("f38ba8").mix(white, 0.5, Space::Oklch).to_oklch();
+        assert!((hue - 2.8).abs() < 1.0);
+        assert_eq!("OKLCH".parse(), Ok(Space::Oklch));
+        assert!("cmyk".parse::<Space>().is_err());
+    }
+
+    #[test]
+    fn contrast() {
+        let (black, white) = (color("000000"), color("ffffff"));
+        assert!((black.contrast(white) - 21.0).abs() < 1e-9);
+        assert!((white.contrast(black) - 21.0).abs() < 1e-9);
+        assert!((white.contrast(white) - 1.0).abs() < 1e-9);
+        let contrast = color("777777").contrast(white);
+        assert!((contrast - 4.48).abs() < 0.01);
+        // half transparent black on white is a mid gray
+        let translucent = color("00000080").contrast(white);
+        assert!((translucent - color("7f7f7f").contrast(white)).abs() < 0.01);
+    }
+
+    #[test]
+    fn apca() {
+        let (gray, white) = (color("888888"), color("ffffff"));
+        assert!((gray.apca(white) - 63.06).abs() < 0.01);
+        assert!((white.apca(gray) + 68.54).abs() < 0.01);
+        assert!((color("000000").apca(white) - 106.04).abs() < 0.01);
+        assert!(gray.apca(gray).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn gradient() {
+        let (base, mauve) = (color("1e1e2e"), color("cba6f7"));
+        let hexes = |colors: Vec<Color>| colors.into_iter().map(Color::to_hex).collect::<Vec<_>>();
+        assert_eq!(
+            hexes(base.gradient(mauve, 3, Space::Srgb)),
+            ["1e1e2e", "756293", "cba6f7"]
+        );
+        let oklab = base.gradient(mauve, 10, Space::Oklab);
+        assert_eq!(oklab.len(), 10);
+        assert_eq!(oklab[0].to_hex(), "1e1e2e");
+        assert_eq!(oklab[9].to_hex(), "cba6f7");
+        assert_eq!(hexes(base.gradient(mauve, 1, Space::Oklab)), ["1e1e2e"]);
+        assert!(base.gradient(mauve, 0, Space::Oklab).is_empty());
+    }
+
+    #[test]
+    fn scale() {
+        let mauve = color("cba6f7");
+        let (_, _, mauve_hue) = mauve.to_oklch();
+        let shades = mauve.scale(11);
+        assert_eq!(shades.len(), 11);
+
+        let lightness: Vec<f64> = shades.iter().map(|shade| shade.to_oklch().0).collect();
+        assert!((lightness[0] - 0.97).abs() < 1e-3);
+        assert!((lightness[10] - 0.26).abs() < 1e-3);
+        assert!(lightness.windows(2).all(|pair| pair[0] > pair[1]));
+        for shade in &shades[1..10] {
+            assert!((shade.to_oklch().2 - mauve_hue).abs() < 1.0);
+        }
+    }
+
+    #[test]
+    fn hsl_adjustments() {
+        let red = color("f38ba8");
+        for adjusted in [
+            red.saturate(0.0),
+            red.desaturate(0.0),
+            red.complement().complement(),
+        ] {
+            assert_eq!(adjusted.to_hex(), "f38ba8");
+        }
+        assert_eq!(red.saturate(0.1).to_css_hsl(), "hsl(343, 91%, 75%)");
+        assert_eq!(red.desaturate(1.0).to_hex(), red.grayscale().to_hex());
+        assert_eq!(red.set_lightness(0.5).to_css_hsl(), "hsl(343, 81%, 50%)");
+        assert_eq!(red.set_hue(120.0).to_css_hsl(), "hsl(120, 81%, 75%)");
+        assert_eq!(red.complement().to_css_hsl(), "hsl(163, 81%, 75%)");
+        assert_eq!(red.grayscale().to_hex(), "bfbfbf");
+        assert_eq!(red.invert().to_hex(), "0c7457");
+        assert_eq!(color("f38ba880").invert().to_hex(), "0c745780");
+    }
+}
@@ -0,0 +1,203 @@
+use serde_json::Value;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("invalid path `{0}`, expected dot-separated keys such as `a.b.c`")]
+    InvalidPath(String),
+
+    #[error("cannot set `{path}`, `{parent}` is not an object or array")]
+    NotAContainer { path: String, parent: String },
+
+    #[error("cannot set `{path}`, index {index} is out of bounds for `{parent}`")]
+    OutOfBounds {
+        path: String,
+        parent: String,
+        index: usize,
+    },
+}
+
+/// Deep merge `overlay` into `base`.
+///
+/// Objects are merged key by key, recursively. Any other value, including
+/// arrays, replaces the value in `base`. Objects whose dot-separated path is
+/// listed in `replace` are also replaced as a whole instead of being merged.
+pub fn merge(base: &mut Value, overlay: Value, replace: &[String]) {
+    merge_at(base, overlay, replace, "");
+}
+
+fn merge_at(base: &mut Value, overlay: Value, replace: &[String], path: &str) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) if !replace.iter().any(|r| r == path) => {
+            for (key, value) in overlay {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                match base.get_mut(&key) {
+                    Some(existing) => merge_at(existing, value, replace, &path),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Deep merge `value` into the value at a dot-separated `path` inside
+/// `target`, such as `colors.selection` or `terminal.ansi.0`, following the
+/// same rules as [`merge`].
+///
+/// Missing objects along the path are created. Numeric keys index into
+/// existing arrays, and are treated as object keys otherwise.
+///
+/// # Errors
+///
+/// Returns an error if the path is malformed, passes through a value that is
+/// not an object or array, or indexes past the end of an array.
+pub fn merge_path(
+    target: &mut Value,
+    path: &str,
+    value: Value,
+    replace: &[String],
+) -> Result<(), Error> {
+    merge_at(lookup_mut(target, path)?, value, replace, path);
+    Ok(())
+}
+
+fn lookup_mut<'a>(target: &'a mut Value, path: &str) -> Result<&'a mut Value, Error> {
+    let keys: Vec<&str> = path.split('.').collect();
+    if keys.iter().any(|key| key.is_empty()) {
+        return Err(Error::InvalidPath(path.to_string()));
+    }
+
+    let mut current = target;
+    for (i, key) in keys.iter().enumerate() {
+        let parent = || keys[..i].join(".");
+        if current.is_null() {
+            *current = Value::Object(serde_json::Map::new());
+        }
+
+        current = match current {
+            Value::Object(map) => map.entry(*key).or_insert(Value::Null),
+            Value::Array(items) => {
+                let Ok(index) = key.parse::<usize>() else {
+                    return Err(Error::NotAContainer {
+                        path: path.to_string(),
+                        parent: parent(),
+                    });
+                };
+                items.get_mut(index).ok_or_else(|| Error::OutOfBounds {
+                    path: path.to_string(),
+                    parent: parent(),
+                    index,
+                })?
+            }
+            _ => {
+                return Err(Error::NotAContainer {
+                    path: path.to_string(),
+                    parent: parent(),
+                })
+            }
+        };
+    }
+
+    Ok(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn top_level_key() {
+        let mut ctx = json!({"a": 1, "b": 2});
+        merge_path(&mut ctx, "a", json!("x"), &[]).expect("valid path");
+        assert_eq!(ctx, json!({"a": "x", "b": 2}));
+    }
+
+    #[test]
+    fn nested_key() {
+        let mut ctx = json!({"colors": {"selection": "a", "cursor": "b"}});
+        merge_path(&mut ctx, "colors.selection", json!("c"), &[]).expect("valid path");
+        assert_eq!(ctx, json!({"colors": {"selection": "c", "cursor": "b"}}));
+    }
+
+    #[test]
+    fn creates_missing_objects() {
+        let mut ctx = json!({});
+        merge_path(&mut ctx, "a.b.0", json!(true), &[]).expect("valid path");
+        assert_eq!(ctx, json!({"a": {"b": {"0": true}}}));
+    }
+
+    #[test]
+    fn array_index() {
+        let mut ctx = json!({"terminal": {"ansi": ["a", "b"]}});
+        merge_path(&mut ctx, "terminal.ansi.1", json!("c"), &[]).expect("valid path");
+        assert_eq!(ctx, json!({"terminal": {"ansi": ["a", "c"]}}));
+    }
+
+    #[test]
+    fn merge_objects() {
+        let mut ctx = json!({"a": 1, "terminal": {"ansi": ["a", "b"], "bg": "x"}});
+        let overlay = json!({"b": 2, "terminal": {"ansi": ["c"], "fg": "y"}});
+        merge(&mut ctx, overlay, &[]);
+        assert_eq!(
+            ctx,
+            json!({"a": 1, "b": 2, "terminal": {"ansi": ["c"], "bg": "x", "fg": "y"}})
+        );
+    }
+
+    #[test]
+    fn merge_replaces_listed_keys() {
+        let mut ctx = json!({"terminal": {"bg": "x", "syntax": {"a": 1, "b": 2}}});
+        let overlay = json!({"terminal": {"fg": "y", "syntax": {"c": 3}}});
+        merge(&mut ctx, overlay, &["terminal.syntax".to_string()]);
+        assert_eq!(
+            ctx,
+            json!({"terminal": {"bg": "x", "fg": "y", "syntax": {"c": 3}}})
+        );
+    }
+
+    #[test]
+    fn merge_at_path() {
+        let mut ctx = json!({"colors": {"selection": "a", "cursor": "b"}});
+        merge_path(&mut ctx, "colors", json!({"selection": "c"}), &[]).expect("valid path");
+        assert_eq!(ctx, json!({"colors": {"selection": "c", "cursor": "b"}}));
+
+        merge_path(
+            &mut ctx,
+            "colors",
+            json!({"x": "d"}),
+            &["colors".to_string()],
+        )
+        .expect("valid path");
+        assert_eq!(ctx, json!({"colors": {"x": "d"}}));
+    }
+
+    #[test]
+    fn invalid_paths() {
+        let mut ctx = json!({"red": "f38ba8", "list": ["a"]});
+        assert!(matches!(
+            merge_path(&mut ctx, "a..b", json!(1), &[]),
+            Err(Error::InvalidPath(_))
+        ));
+        assert!(matches!(
+            merge_path(&mut ctx, "red.x", json!(1), &[]),
+            Err(Error::NotAContainer { .. })
+        ));
+        assert!(matches!(
+            merge_path(&mut ctx, "list.x", json!(1), &[]),
+            Err(Error::NotAContainer { .. })
+        ));
+        assert!(matches!(
+            merge_path(&mut ctx, "list.1", json!(1), &[]),
+            Err(Error::OutOfBounds { index: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,190 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::color::Color;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("unknown color format `{0}`, expected one of: {names}", names = NAMES.join(", "))]
+    UnknownFormat(String),
+}
+
+const NAMES: [&str; 7] = ["hex", "hash", "upper", "0x", "rgb", "hsl", "float"];
+
+/// How colors are written in the rendered output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ColorFormat {
+    /// Lowercase hex without a prefix, such as `f38ba8`.
+    #[default]
+    Hex,
+    /// Lowercase hex with a `#` prefix, such as `#f38ba8`.
+    Hash,
+    /// Uppercase hex without a prefix, such as `F38BA8`.
+    Upper,
+    /// Uppercase hex with a `0x` prefix, such as `0xF38BA8`.
+    ZeroX,
+    /// CSS RGB, such as `rgb(243, 139, 168)`, or RGBA for translucent colors.
+    Rgb,
+    /// CSS HSL, such as `hsl(343, 81%, 75%)`, or HSLA for translucent colors.
+    Hsl,
+    /// RGBA components as floats from 0 to 1, such as `0.953, 0.545, 0.659, 1.000`.
+    Float,
+}
+
+impl FromStr for ColorFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(Self::Hex),
+            "hash" => Ok(Self::Hash),
+            "upper" => Ok(Self::Upper),
+            "0x" => Ok(Self::ZeroX),
+            "rgb" => Ok(Self::Rgb),
+            "hsl" => Ok(Self::Hsl),
+            "float" => Ok(Self::Float),
+            _ => Err(Error::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+impl TryFrom<String> for ColorFormat {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl ColorFormat {
+    /// Write the hex color in this format. Returns `None` if `hex` isn't a
+    /// hex color.
+    #[must_use]
+    pub fn format(self, hex: &str) -> Option<String> {
+        let hex = crate::parse::normalize_hex(hex).ok()?;
+        let color = Color::from_hex(&hex).ok()?;
+        let opaque = color.a >= 1.0;
+
+        Some(match self {
+            Self::Hex => hex.to_ascii_lowercase(),
+            Self::Hash => format!("#{}", hex.to_ascii_lowercase()),
+            Self::Upper => hex.to_ascii_uppercase(),
+            Self::ZeroX => format!("0x{}", hex.to_ascii_uppercase()),
+            Self::Rgb if opaque => color.to_css_rgb(),
+            Self::Rgb => color.to_css_rgba(),
+            Self::Hsl if opaque => color.to_css_hsl(),
+            Self::Hsl => color.to_css_hsla(),
+            Self::Float => format!(
+                "{:.3}, {:.3}, {:.3}, {:.3}",
+                color.r, color.g, color.b, color.a
+            ),
+        })
+    }
+
+    /// Write every color marked with [`mark`] in `output` in this format.
+    /// [`ColorFormat::Hex`] only removes the marks, leaving the colors as
+    /// they are.
+    #[must_use]
+    pub fn apply(self, output: &str) -> String {
+        let mut result = String::with_capacity(output.len());
+        let mut rest = output;
+        while let Some(start) = rest.find(MARK_START) {
+            result.push_str(&rest[..start]);
+            let marked = &rest[start + MARK_START.len_utf8()..];
+            let Some(end) = marked.find(MARK_END) else {
+                rest = marked;
+                break;
+            };
+            let color = &marked[..end];
+            match self {
+                Self::Hex => result.push_str(color),
+                _ => result.push_str(self.format(color).as_deref().unwrap_or(color)),
+            }
+            rest = &marked[end + MARK_END.len_utf8()..];
+        }
+        result.push_str(rest);
+        result.replace([MARK_START, MARK_END], "")
+    }
+}
+
+/// Marks around a color in the context or in a helper's result, so that the
+/// color, and only the color, is written in the chosen format once rendered.
+/// These are Unicode private use characters, which can't appear in a palette
+/// or a hex code.
+const MARK_START: char = '\u{E000}';
+const MARK_END: char = '\u{E001}';
+
+/// Mark `hex` as a color for [`ColorFormat::apply`].
+#[must_use]
+pub fn mark(hex: &str) -> String {
+    format!("{MARK_START}{hex}{MARK_END}")
+}
+
+/// The color inside a value marked with [`mark`], if it is marked.
+#[must_use]
+pub fn unmark(value: &str) -> Option<&str> {
+    value.strip_prefix(MARK_START)?.strip_suffix(MARK_END)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let cases = [
+            (ColorFormat::Hex, "f38ba8"),
+            (ColorFormat::Hash, "#f38ba8"),
+            (ColorFormat::Upper, "F38BA8"),
+            (ColorFormat::ZeroX, "0xF38BA8"),
+            (ColorFormat::Rgb, "rgb(243, 139, 168)"),
+            (ColorFormat::Hsl, "hsl(343, 81%, 75%)"),
+            (ColorFormat::Float, "0.953, 0.545, 0.659, 1.000"),
+        ];
+        for (format, expected) in cases {
+            assert_eq!(format.format("f38ba8").as_deref(), Some(expected));
+        }
+        assert_eq!(
+            ColorFormat::Rgb.format("f38ba880").as_deref(),
+            Some("rgba(243, 139, 168, 0.50)")
+        );
+        assert_eq!(ColorFormat::Hash.format("#FFF").as_deref(), Some("#ffffff"));
+        assert_eq!(ColorFormat::Hash.format("nope"), None);
+    }
+
+    #[test]
+    fn apply() {
+        let output = format!("bg = {}, width = 100000, date = 20240101", mark("1e1e2e"));
+        assert_eq!(
+            ColorFormat::Hash.apply(&output),
+            "bg = #1e1e2e, width = 100000, date = 20240101"
+        );
+        assert_eq!(
+            ColorFormat::Rgb.apply(&output),
+            "bg = rgb(30, 30, 46), width = 100000, date = 20240101"
+        );
+        assert_eq!(
+            ColorFormat::Hex.apply(&output),
+            "bg = 1e1e2e, width = 100000, date = 20240101"
+        );
+        assert_eq!(ColorFormat::Hash.apply(&mark("F38BA8")), "#f38ba8");
+        assert_eq!(ColorFormat::Hex.apply(&mark("F38BA8")), "F38BA8");
+        assert_eq!(ColorFormat::Hash.apply(&mark("nope")), "nope");
+        assert_eq!(unmark(&mark("1e1e2e")), Some("1e1e2e"));
+        assert_eq!(unmark("1e1e2e"), None);
+    }
+
+    #[test]
+    fn parse() {
+        for name in NAMES {
+            assert!(name.parse::<ColorFormat>().is_ok());
+        }
+        assert_eq!(
+            "cmyk".parse::<ColorFormat>(),
+            Err(Error::UnknownFormat("cmyk".to_string()))
+        );
+    }
+}
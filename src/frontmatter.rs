@@ -1,5 +1,47 @@
 use handlebars::Handlebars;
+use serde::Deserialize;
 use serde_json::Value;
+use thiserror::Error;
+
+use crate::audit::ContrastPair;
+use crate::context;
+use crate::format::ColorFormat;
+use crate::matrix::Dimension;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("frontmatter keys depend on each other in a cycle ({})", .0.join(" -> "))]
+    Cycle(Vec<String>),
+
+    #[error("failed to parse the `whiskers` block of the frontmatter: {0}")]
+    Config(String),
+}
+
+/// Settings for whiskers itself, read from the `whiskers` key of the frontmatter.
+///
+/// This block is not rendered along with the rest of the frontmatter. Templated
+/// values such as `filename` are rendered later against the merged context.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Config {
+    /// Template for the path to write the rendered output to.
+    pub filename: Option<String>,
+
+    /// Dimensions to render the template across, once per combination.
+    pub matrix: Option<Vec<Dimension>>,
+
+    /// Dot-separated paths of objects that replace, rather than merge with,
+    /// the same object from an earlier layer of the context.
+    #[serde(default)]
+    pub replace: Vec<String>,
+
+    /// How colors are written in the rendered output.
+    #[serde(rename = "colorFormat")]
+    pub color_format: Option<ColorFormat>,
+
+    /// Foreground and background pairs checked by `--audit`.
+    #[serde(default)]
+    pub contrast: Vec<ContrastPair>,
+}
 
 fn split(template: &str) -> Option<(&str, &str)> {
     // we consider a template to possibly have frontmatter iff:
@@ -16,38 +58,322 @@ fn split(template: &str) -> Option<(&str, &str)> {
         .map(|(a, b)| (a.trim(), b.trim()))
 }
 
-#[must_use]
-pub fn render_and_parse<'a>(
-    template: &'a str,
-    reg: &Handlebars,
-    ctx: &Value,
-) -> (&'a str, Option<Value>) {
-    let Some((frontmatter, content)) = split(template) else {
-        return (template, None);
+fn split_config(frontmatter: &str) -> (String, String) {
+    // the config block is the top-level `whiskers` key and every indented or
+    // blank line that follows it.
+    let mut rest = String::new();
+    let mut config = String::new();
+    let mut in_config = false;
+    for line in frontmatter.lines() {
+        if line.starts_with("whiskers:") {
+            in_config = true;
+        } else if !line.is_empty() && !line.starts_with(char::is_whitespace) {
+            in_config = false;
+        }
+
+        let target = if in_config { &mut config } else { &mut rest };
+        target.push_str(line);
+        target.push('\n');
+    }
+    (rest, config)
+}
+
+#[derive(Deserialize)]
+struct ConfigBlock {
+    #[serde(default)]
+    whiskers: Config,
+}
+
+/// Read the whiskers [`Config`] from the template's frontmatter, if it has any.
+///
+/// # Errors
+///
+/// Returns an error if the `whiskers` block is not valid YAML or has invalid
+/// settings, since rendering without them could write the wrong files.
+pub fn config(template: &str) -> Result<Config, Error> {
+    let Some((frontmatter, _)) = split(template) else {
+        return Ok(Config::default());
     };
 
+    let (_, config) = split_config(frontmatter);
+    if config.is_empty() {
+        return Ok(Config::default());
+    }
+
+    serde_yaml::from_str::<ConfigBlock>(&config)
+        .map(|block| block.whiskers)
+        .map_err(|e| Error::Config(e.to_string()))
+}
+
+/// Split the frontmatter into its top-level keys and the YAML source of each.
+///
+/// Returns `None` if the frontmatter can't be split, for example if its
+/// structure is generated by a template.
+fn split_keys(frontmatter: &str) -> Option<Vec<(String, String)>> {
+    let key_pattern = regex::Regex::new(r#"^(?:"([^"]*)"|'([^']*)'|(\w[\w-]*))[ \t]*:(?:\s|$)"#)
+        .expect("regex is valid");
+
+    let mut keys: Vec<(String, String)> = vec![];
+    for line in frontmatter.lines() {
+        let continues = line.is_empty()
+            || line.starts_with(char::is_whitespace)
+            || line.starts_with('#')
+            || line.starts_with('-');
+        if continues {
+            if let Some((_, source)) = keys.last_mut() {
+                source.push_str(line);
+                source.push('\n');
+            } else if !line.trim().is_empty() && !line.starts_with('#') {
+                return None;
+            }
+            continue;
+        }
+
+        let caps = key_pattern.captures(line)?;
+        let key = caps
+            .get(1)
+            .or_else(|| caps.get(2))
+            .or_else(|| caps.get(3))?;
+        keys.push((key.as_str().to_string(), format!("{line}\n")));
+    }
+    Some(keys)
+}
+
+/// Find the names referenced by the template expressions in `source`.
+///
+/// Only the first part of each path counts, so `colors.base.hex` references
+/// `colors`. Helper names, hash keys, block parameters, and literals are not
+/// references.
+fn references(source: &str) -> Vec<String> {
+    let expression = regex::Regex::new(r"(?s)\{\{(.*?)\}\}").expect("regex is valid");
+    let literal = regex::Regex::new(r#""[^"]*"|'[^']*'"#).expect("regex is valid");
+    let token = regex::Regex::new(r"[()]|[^\s()]+").expect("regex is valid");
+
+    let mut names = vec![];
+    for caps in expression.captures_iter(source) {
+        let expr = caps[1].trim_matches(|c: char| c == '~' || c == '{' || c == '}');
+        // comments, partials, and the closing tags of blocks reference nothing
+        if expr.starts_with(['!', '/', '>']) {
+            continue;
+        }
+        let expr = literal.replace_all(expr.trim_start_matches(['#', '^', '&']), " ");
+        let mut tokens = token.find_iter(&expr).map(|t| t.as_str());
+        group_references(&mut tokens, &mut names);
+    }
+    names
+}
+
+/// Collect the references of one expression or subexpression, up to its
+/// closing parenthesis.
+fn group_references<'a>(tokens: &mut impl Iterator<Item = &'a str>, names: &mut Vec<String>) {
+    let mut words = vec![];
+    let mut items = 0;
+    while let Some(token) = tokens.next() {
+        match token {
+            "(" => group_references(tokens, names),
+            ")" => break,
+            word => words.push((items, word)),
+        }
+        items += 1;
+    }
+
+    for (i, word) in words {
+        // the first of several items is the helper being called
+        if i == 0 && items > 1 {
+            continue;
+        }
+        // only the value of a hash argument such as `space=oklab`
+        let word = word.split_once('=').map_or(word, |(_, value)| value);
+        let word = word.strip_prefix("this.").unwrap_or(word);
+        let word = word.strip_prefix("./").unwrap_or(word);
+        let first = word.split(['.', '/']).next().unwrap_or_default();
+        let first = first.trim_start_matches('[').trim_end_matches(']');
+
+        let is_name = first.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && !word.starts_with("..")
+            && !matches!(
+                first,
+                "this" | "as" | "else" | "true" | "false" | "null" | "undefined"
+            );
+        if is_name {
+            names.push(first.to_string());
+        }
+    }
+}
+
+/// Order the keys so that each comes after the keys it references, keeping
+/// the original order where possible. Returns the keys forming a cycle if
+/// there is one.
+fn dependency_order(keys: &[(String, String)]) -> Result<Vec<usize>, Vec<String>> {
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum State {
+        Unvisited,
+        Visiting,
+        Done,
+    }
+
+    fn visit(
+        i: usize,
+        deps: &[Vec<usize>],
+        state: &mut [State],
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), Vec<usize>> {
+        match state[i] {
+            State::Done => return Ok(()),
+            State::Visiting => {
+                let start = path.iter().position(|&p| p == i).unwrap_or(0);
+                let mut cycle = path[start..].to_vec();
+                cycle.push(i);
+                return Err(cycle);
+            }
+            State::Unvisited => {}
+        }
+
+        state[i] = State::Visiting;
+        path.push(i);
+        for &dep in &deps[i] {
+            visit(dep, deps, state, path, order)?;
+        }
+        path.pop();
+        state[i] = State::Done;
+        order.push(i);
+        Ok(())
+    }
+
+    // a key referencing itself refers to the value from the context, not a cycle.
+    let deps: Vec<Vec<usize>> = keys
+        .iter()
+        .enumerate()
+        .map(|(i, (_, source))| {
+            let names = references(source);
+            keys.iter()
+                .enumerate()
+                .filter(|&(j, (key, _))| j != i && names.contains(key))
+                .map(|(j, _)| j)
+                .collect()
+        })
+        .collect();
+
+    let mut state = vec![State::Unvisited; keys.len()];
+    let mut order = Vec::with_capacity(keys.len());
+    for i in 0..keys.len() {
+        visit(i, &deps, &mut state, &mut vec![], &mut order).map_err(|cycle| {
+            cycle
+                .into_iter()
+                .map(|j| keys[j].0.clone())
+                .collect::<Vec<_>>()
+        })?;
+    }
+    Ok(order)
+}
+
+fn render_whole(frontmatter: &str, reg: &Handlebars, ctx: &Value) -> Option<Value> {
     let frontmatter = match reg.render_template(frontmatter, ctx) {
         Ok(frontmatter) => frontmatter,
         Err(e) => {
             eprintln!(
                 "warning: Failed to render frontmatter templates ({e}). Proceeding without it"
             );
-            return (content, None);
+            return None;
         }
     };
 
-    match serde_yaml::from_str(&frontmatter) {
-        Ok(frontmatter) => (content, Some(frontmatter)),
+    match serde_yaml::from_str::<Value>(&frontmatter) {
+        Ok(frontmatter @ Value::Object(_)) => Some(frontmatter),
+        Ok(_) => {
+            eprintln!("warning: YAML frontmatter is not a mapping. Proceeding without it.");
+            None
+        }
         Err(e) => {
             eprintln!("warning: Failed to parse YAML frontmatter ({e}). Proceeding without it.");
-            (content, None)
+            None
+        }
+    }
+}
+
+/// Render the template's frontmatter and parse it as YAML, returning the
+/// remaining template content alongside it.
+///
+/// Each top-level key is rendered separately, in dependency order, so that
+/// values can be derived from other keys in the frontmatter. Keys listed in
+/// `overridden` are already set in `ctx` and are not rendered at all, so that
+/// keys derived from them pick up the overridden value.
+///
+/// Each rendered key is deep merged into the context the next keys are
+/// rendered against, following `replace` as in [`context::merge`], so that a
+/// key extending a built-in object such as `colors` keeps the rest of it.
+///
+/// # Errors
+///
+/// Returns an error if keys reference each other in a cycle, since none of
+/// them can be rendered. Other problems with the frontmatter are reported as
+/// warnings, and the template is rendered without it.
+pub fn render_and_parse<'a>(
+    template: &'a str,
+    reg: &Handlebars,
+    ctx: &Value,
+    overridden: &[String],
+    replace: &[String],
+) -> Result<(&'a str, Option<Value>), Error> {
+    let Some((frontmatter, content)) = split(template) else {
+        return Ok((template, None));
+    };
+
+    let (frontmatter, _) = split_config(frontmatter);
+
+    let Some(keys) = split_keys(&frontmatter) else {
+        return Ok((content, render_whole(&frontmatter, reg, ctx)));
+    };
+
+    let order = dependency_order(&keys).map_err(Error::Cycle)?;
+
+    let mut resolved = ctx.clone();
+    let mut values = vec![None; keys.len()];
+    for i in order {
+        let (key, source) = &keys[i];
+        if overridden.contains(key) {
+            continue;
         }
+
+        let rendered = match reg.render_template(source, &resolved) {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                eprintln!(
+                    "warning: Failed to render frontmatter templates ({e}). Proceeding without it"
+                );
+                return Ok((content, None));
+            }
+        };
+
+        // anything unusual, such as YAML anchors shared between keys, is left
+        // to be rendered and parsed as a whole.
+        let Ok(Value::Object(mut parsed)) = serde_yaml::from_str::<Value>(&rendered) else {
+            return Ok((content, render_whole(&frontmatter, reg, ctx)));
+        };
+        let Some(value) = parsed.remove(key) else {
+            return Ok((content, render_whole(&frontmatter, reg, ctx)));
+        };
+
+        let mut layer = serde_json::Map::new();
+        layer.insert(key.clone(), value.clone());
+        context::merge(&mut resolved, Value::Object(layer), replace);
+        values[i] = Some(value);
     }
+
+    // keys are rendered in dependency order, but kept in the order they were written
+    let result = keys
+        .into_iter()
+        .zip(values)
+        .filter_map(|((key, _), value)| Some((key, value?)))
+        .collect();
+    Ok((content, Some(Value::Object(result))))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::template::{make_context, make_registry, FLAVORS};
 
     #[test]
     fn no_frontmatter() {
@@ -84,7 +410,7 @@ mod tests {
             serde_json::from_str::<Value>(r#"{"a":"b","c":"d"}"#).expect("valid json fixture");
         let reg = Handlebars::new();
         let ctx = Value::Object(serde_json::Map::new());
-        let result = render_and_parse(content, &reg, &ctx);
+        let result = render_and_parse(content, &reg, &ctx, &[], &[]).expect("frontmatter renders");
         assert_eq!(result, ("a: b\nc: d", Some(expected)));
     }
 
@@ -95,7 +421,212 @@ mod tests {
             serde_json::from_str::<Value>(r#"{"a":"b","c":"d"}"#).expect("valid json fixture");
         let reg = Handlebars::new();
         let ctx = serde_json::from_str::<Value>(r#"{"var":"b"}"#).expect("valid json fixture");
-        let result = render_and_parse(content, &reg, &ctx);
+        let result = render_and_parse(content, &reg, &ctx, &[], &[]).expect("frontmatter renders");
         assert_eq!(result, ("a: b\nc: d", Some(expected)));
     }
+
+    #[test]
+    fn config_is_not_rendered() {
+        let content = "---\na: {{var}}\nwhiskers:\n  filename: \"{{a}}.txt\"\nc: d\n---\nbody";
+        let expected =
+            serde_json::from_str::<Value>(r#"{"a":"b","c":"d"}"#).expect("valid json fixture");
+        let mut reg = Handlebars::new();
+        reg.set_strict_mode(true);
+        let ctx = serde_json::from_str::<Value>(r#"{"var":"b"}"#).expect("valid json fixture");
+        let result = render_and_parse(content, &reg, &ctx, &[], &[]).expect("frontmatter renders");
+        assert_eq!(result, ("body", Some(expected)));
+        assert_eq!(
+            config(content).expect("valid config"),
+            Config {
+                filename: Some("{{a}}.txt".to_string()),
+                ..Config::default()
+            }
+        );
+    }
+
+    #[test]
+    fn color_format_config() {
+        let content = "---\nwhiskers:\n  colorFormat: 0x\n---\nbody";
+        assert_eq!(
+            config(content).expect("valid config").color_format,
+            Some(ColorFormat::ZeroX)
+        );
+    }
+
+    #[test]
+    fn contrast_config() {
+        let content = "---\nwhiskers:\n  contrast:\n    - { fg: text, bg: base, min: 7 }\n    - fg: '{{subtext0}}'\n      bg: surface0\n---\nbody";
+        assert_eq!(
+            config(content).expect("valid config").contrast,
+            vec![
+                ContrastPair {
+                    fg: "text".to_string(),
+                    bg: "base".to_string(),
+                    min: 7.0,
+                },
+                ContrastPair {
+                    fg: "{{subtext0}}".to_string(),
+                    bg: "surface0".to_string(),
+                    min: 4.5,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_config() {
+        assert_eq!(config("---\na: b\n---\nbody"), Ok(Config::default()));
+        assert_eq!(config("body"), Ok(Config::default()));
+    }
+
+    #[test]
+    fn invalid_config() {
+        for content in [
+            "---\nwhiskers:\n  colorFormat: hsh\n  filename: out.txt\n---\nbody",
+            "---\nwhiskers:\n  contrast:\n    - { fg: text, bg: base, min: \"7\" }\n---\nbody",
+            "---\nwhiskers:\n  matrix: flavor\n---\nbody",
+        ] {
+            assert!(
+                matches!(config(content), Err(Error::Config(_))),
+                "{content}"
+            );
+        }
+    }
+
+    fn strict_registry() -> Handlebars<'static> {
+        let mut reg = Handlebars::new();
+        reg.set_strict_mode(true);
+        reg
+    }
+
+    #[test]
+    fn keys_reference_other_keys() {
+        let content = "---\nc: \"{{b}}-c\"\n# comment\na: \"{{var}}\"\nb: \"{{a}}-b\"\n---\nbody";
+        let expected = serde_json::from_str::<Value>(r#"{"a":"x","b":"x-b","c":"x-b-c"}"#)
+            .expect("valid json fixture");
+        let ctx = serde_json::from_str::<Value>(r#"{"var":"x"}"#).expect("valid json fixture");
+        let result = render_and_parse(content, &strict_registry(), &ctx, &[], &[])
+            .expect("frontmatter renders");
+        assert_eq!(result, ("body", Some(expected)));
+    }
+
+    #[test]
+    fn overridden_keys_are_not_rendered() {
+        let content = "---\na: \"{{var}}\"\nb: \"{{a}}-b\"\n---\nbody";
+        let expected = serde_json::from_str::<Value>(r#"{"b":"y-b"}"#).expect("valid json fixture");
+        let ctx =
+            serde_json::from_str::<Value>(r#"{"var":"x","a":"y"}"#).expect("valid json fixture");
+        let result = render_and_parse(content, &strict_registry(), &ctx, &["a".to_string()], &[])
+            .expect("frontmatter renders");
+        assert_eq!(result, ("body", Some(expected)));
+    }
+
+    #[test]
+    fn key_order_is_kept() {
+        let content = "---\nz: \"{{m}}\"\nm: \"{{a}}\"\na: x\n---\nbody";
+        let (_, frontmatter) =
+            render_and_parse(content, &strict_registry(), &Value::Null, &[], &[])
+                .expect("frontmatter renders");
+        let frontmatter = frontmatter.expect("frontmatter is rendered");
+        let keys: Vec<&String> = frontmatter
+            .as_object()
+            .expect("frontmatter is an object")
+            .keys()
+            .collect();
+        assert_eq!(keys, ["z", "m", "a"]);
+    }
+
+    #[test]
+    fn keys_extending_objects_keep_the_rest() {
+        let content =
+            "---\ncolors:\n  selection: \"{{surface2}}\"\nfg: \"{{colors.red.hex}}\"\n---\nbody";
+        let expected =
+            serde_json::from_str::<Value>(r#"{"colors":{"selection":"585b70"},"fg":"f38ba8"}"#)
+                .expect("valid json fixture");
+        let ctx = serde_json::from_str::<Value>(
+            r#"{"surface2":"585b70","colors":{"red":{"hex":"f38ba8"}}}"#,
+        )
+        .expect("valid json fixture");
+        let result = render_and_parse(content, &strict_registry(), &ctx, &[], &[])
+            .expect("frontmatter renders");
+        assert_eq!(result, ("body", Some(expected)));
+    }
+
+    #[test]
+    fn self_reference_is_not_a_cycle() {
+        let keys = split_keys("a: \"{{a}}\"\nb: \"{{lookup this a}}\"").expect("splittable");
+        assert_eq!(dependency_order(&keys), Ok(vec![0, 1]));
+    }
+
+    #[test]
+    fn cycle() {
+        let keys = split_keys("a: \"{{b}}\"\nb: \"{{ darken c 0.1 }}\"\nc: \"{{a}}\"\nd: x")
+            .expect("splittable");
+        assert_eq!(
+            dependency_order(&keys),
+            Err(vec![
+                "a".to_string(),
+                "b".to_string(),
+                "c".to_string(),
+                "a".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn cycle_is_an_error() {
+        let content = "---\na: \"{{b}}\"\nb: \"{{a}}\"\n---\nbody";
+        assert_eq!(
+            render_and_parse(content, &strict_registry(), &Value::Null, &[], &[]),
+            Err(Error::Cycle(vec![
+                "a".to_string(),
+                "b".to_string(),
+                "a".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn path_parts_and_helpers_are_not_references() {
+        assert_eq!(references("{{colors.base.hex}}"), ["colors"]);
+        assert_eq!(
+            references("{{mix a (darken b 0.1) 0.5 space=\"oklab\"}}"),
+            ["b", "a"]
+        );
+        assert_eq!(
+            references("{{readable_on base text min=ratio}}"),
+            ["base", "text", "ratio"]
+        );
+        assert_eq!(
+            references("{{#each palette as |color|}}{{color.hex}}{{@index}}{{/each}}"),
+            ["palette", "color"]
+        );
+
+        let content =
+            "---\nsurface: \"{{colors.base.hex}}\"\nbase: \"{{darken surface 0.1}}\"\n---\nbody";
+        let ctx = make_context(&FLAVORS, Some(&FLAVORS[3]));
+        let (_, frontmatter) =
+            render_and_parse(content, &make_registry(), &ctx, &[], &[]).expect("not a cycle");
+        let frontmatter = frontmatter.expect("frontmatter is rendered");
+        assert_eq!(frontmatter["surface"], "1e1e2e");
+        assert_eq!(frontmatter["base"], "0a0a0f");
+    }
+
+    #[test]
+    fn string_literals_are_not_references() {
+        let keys =
+            split_keys("a: \"{{b}}\"\nb: '{{ darklight \"a\" \"c\" }}'\nc: x").expect("splittable");
+        assert_eq!(dependency_order(&keys), Ok(vec![1, 0, 2]));
+    }
+
+    #[test]
+    fn generated_structure_is_rendered_whole() {
+        let content = "---\n{{#if var}}\na: b\n{{/if}}\n---\nbody";
+        assert_eq!(split_keys("{{#if var}}\na: b\n{{/if}}"), None);
+        let expected = serde_json::from_str::<Value>(r#"{"a":"b"}"#).expect("valid json fixture");
+        let ctx = serde_json::from_str::<Value>(r#"{"var":true}"#).expect("valid json fixture");
+        let result = render_and_parse(content, &strict_registry(), &ctx, &[], &[])
+            .expect("frontmatter renders");
+        assert_eq!(result, ("body", Some(expected)));
+    }
 }
@@ -1,79 +1,351 @@
 use base64::Engine;
-use css_colors::{Color, Ratio, HSLA};
 use handlebars::{
-    handlebars_helper, Context, Handlebars, Helper, HelperResult, Output, RenderContext,
-    RenderError,
+    handlebars_helper, Context, Handlebars, Helper, HelperResult, Output, PathAndJson,
+    RenderContext, RenderError,
 };
 
 use ::titlecase::titlecase as titlecase_ext;
 use serde_json::Value;
 
-use crate::parse::HSLAExt;
+use crate::color::{Color, Space};
 
 impl From<crate::parse::Error> for RenderError {
     fn from(value: crate::parse::Error) -> Self {
-        Self::from_error("Failed to parse hex string", value)
+        Self::from_error(&value.to_string(), value)
     }
 }
 
-handlebars_helper!(uppercase: |s: String| s.to_uppercase());
-handlebars_helper!(lowercase: |s: String| s.to_lowercase());
-handlebars_helper!(titlecase: |s: String| titlecase_ext(&s));
+/// A parameter of a helper defined with [`color_helper!`].
+///
+/// `marked` is set if the parameter is a color marked with
+/// [`format::mark`](crate::format::mark), so that colors made from it are
+/// marked too.
+trait Param: Sized {
+    fn from_param(
+        param: &PathAndJson,
+        ctx: &Context,
+        rc: &RenderContext,
+        marked: &mut bool,
+    ) -> Result<Self, RenderError>;
+}
+
+impl Param for f64 {
+    fn from_param(
+        param: &PathAndJson,
+        _: &Context,
+        _: &RenderContext,
+        _: &mut bool,
+    ) -> Result<Self, RenderError> {
+        param
+            .value()
+            .as_f64()
+            .ok_or_else(|| RenderError::new("expected a number"))
+    }
+}
+
+impl Param for u32 {
+    fn from_param(
+        param: &PathAndJson,
+        _: &Context,
+        _: &RenderContext,
+        _: &mut bool,
+    ) -> Result<Self, RenderError> {
+        param
+            .value()
+            .as_u64()
+            .and_then(|n| n.try_into().ok())
+            .ok_or_else(|| RenderError::new("expected a whole number"))
+    }
+}
+
+/// Colors can be hex codes, CSS color functions, or the names of colors in
+/// the context, such as `"red"`. If a string is neither a color nor the name
+/// of one, the error from parsing it as a color is returned.
+impl Param for Color {
+    fn from_param(
+        param: &PathAndJson,
+        ctx: &Context,
+        rc: &RenderContext,
+        marked: &mut bool,
+    ) -> Result<Self, RenderError> {
+        let mut parse = |color: &str| {
+            let parsed = crate::parse::parse_color(color)?;
+            *marked |= crate::format::unmark(color).is_some();
+            Ok::<_, crate::parse::Error>(parsed)
+        };
+        let color = param
+            .value()
+            .as_str()
+            .ok_or_else(|| RenderError::new("expected a color"))?;
+        match parse(color) {
+            Ok(parsed) => Ok(parsed),
+            Err(e) => match rc.evaluate(ctx, color) {
+                Ok(named) => match named.as_json().as_str() {
+                    Some(named) => Ok(parse(named)?),
+                    None => Err(e.into()),
+                },
+                Err(_) => Err(e.into()),
+            },
+        }
+    }
+}
+
+impl Param for Space {
+    fn from_param(
+        param: &PathAndJson,
+        _: &Context,
+        _: &RenderContext,
+        _: &mut bool,
+    ) -> Result<Self, RenderError> {
+        param
+            .value()
+            .as_str()
+            .ok_or_else(|| RenderError::new("expected a color space"))?
+            .parse()
+            .map_err(RenderError::new)
+    }
+}
+
+/// The result of a helper defined with [`color_helper!`]. Colors are written
+/// as hex, marked if any color they were made from was marked.
+trait HelperValue {
+    fn into_value(self, marked: bool) -> Value;
+}
+
+impl HelperValue for Color {
+    fn into_value(self, marked: bool) -> Value {
+        if marked {
+            crate::format::mark(&self.to_hex()).into()
+        } else {
+            self.to_hex().into()
+        }
+    }
+}
+
+impl HelperValue for Vec<Color> {
+    fn into_value(self, marked: bool) -> Value {
+        self.into_iter()
+            .map(|color| color.into_value(marked))
+            .collect()
+    }
+}
+
+macro_rules! plain_helper_value {
+    ($($tpe:ty),*) => {
+        $(impl HelperValue for $tpe {
+            fn into_value(self, _: bool) -> Value {
+                Value::from(self)
+            }
+        })*
+    };
+}
+
+plain_helper_value!(String, f64, u8);
+
+/// Define a helper like [`handlebars_helper!`], but with parameters that are
+/// read through [`Param`], so that colors can be given in any form.
+macro_rules! color_helper {
+    ($struct_name:ident: |$($name:ident: $tpe:ty),*
+        $(, *$rest_name:ident: $rest_tpe:ty)?
+        $(, { $($hash_name:ident: $hash_tpe:ty = $hash_default:expr),+ })?| $body:expr) => {
+        #[allow(non_camel_case_types)]
+        pub struct $struct_name;
+
+        impl handlebars::HelperDef for $struct_name {
+            #[allow(unused_assignments)]
+            fn call_inner<'reg: 'rc, 'rc>(
+                &self,
+                h: &Helper<'reg, 'rc>,
+                _: &'reg Handlebars<'reg>,
+                ctx: &'rc Context,
+                rc: &mut RenderContext<'reg, 'rc>,
+            ) -> Result<handlebars::ScopedJson<'reg, 'rc>, RenderError> {
+                let mut param_idx = 0;
+                let mut marked = false;
+                $(
+                    let param = h.param(param_idx).ok_or_else(|| RenderError::new(format!(
+                        "`{}` helper: Couldn't read parameter {}",
+                        stringify!($struct_name), stringify!($name),
+                    )))?;
+                    let $name = <$tpe as Param>::from_param(param, ctx, rc, &mut marked).map_err(|e| {
+                        RenderError::from_error(&format!(
+                            "`{}` helper: Couldn't read parameter {} as {}, got {}: {}",
+                            stringify!($struct_name), stringify!($name), stringify!($tpe),
+                            param.value(), e.desc,
+                        ), e)
+                    })?;
+                    param_idx += 1;
+                )*
+                $(
+                    let $rest_name = h.params()[param_idx..].iter().map(|param| {
+                        <$rest_tpe as Param>::from_param(param, ctx, rc, &mut marked).map_err(|e| {
+                            RenderError::from_error(&format!(
+                                "`{}` helper: Couldn't read parameter {} as {}, got {}: {}",
+                                stringify!($struct_name), stringify!($rest_name),
+                                stringify!($rest_tpe), param.value(), e.desc,
+                            ), e)
+                        })
+                    }).collect::<Result<Vec<_>, _>>()?;
+                )?
+                $($(
+                    let $hash_name = match h.hash_get(stringify!($hash_name)) {
+                        Some(param) => <$hash_tpe as Param>::from_param(param, ctx, rc, &mut marked)
+                            .map_err(|e| RenderError::from_error(&format!(
+                                "`{}` helper: Couldn't read hash parameter {} as {}, got {}: {}",
+                                stringify!($struct_name), stringify!($hash_name),
+                                stringify!($hash_tpe), param.value(), e.desc,
+                            ), e))?,
+                        None => $hash_default,
+                    };
+                )+)?
+
+                let result = $body;
+                Ok(handlebars::ScopedJson::Derived(HelperValue::into_value(result, marked)))
+            }
+        }
+    };
+}
+
+/// The text of a string, without the [`format::mark`](crate::format::mark)
+/// around it if it is a marked color, so that templates see plain hex.
+fn plain(s: &str) -> &str {
+    crate::format::unmark(s).unwrap_or(s)
+}
+
+/// A value with any [`format::mark`](crate::format::mark) removed, for
+/// comparing it.
+fn plain_json(value: &Value) -> Value {
+    match value {
+        Value::String(s) => plain(s).into(),
+        value => value.clone(),
+    }
+}
+
+handlebars_helper!(uppercase: |s: String| plain(&s).to_uppercase());
+handlebars_helper!(lowercase: |s: String| plain(&s).to_lowercase());
+handlebars_helper!(titlecase: |s: String| titlecase_ext(plain(&s)));
+// replace the built-in `eq` and `ne`, which would compare marked colors
+handlebars_helper!(eq: |a: Value, b: Value| plain_json(&a) == plain_json(&b));
+handlebars_helper!(ne: |a: Value, b: Value| plain_json(&a) != plain_json(&b));
 handlebars_helper!(trunc: |number: f32, places: usize| format!("{number:.places$}"));
-handlebars_helper!(lighten: |color: String, weight: f32| {
-    HSLA::from_hex(&color)?.lighten(Ratio::from_f32(weight)).to_hex()
+color_helper!(lighten: |color: Color, weight: f64| {
+    color.lighten(weight)
+});
+color_helper!(darken: |color: Color, weight: f64| {
+    color.darken(weight)
+});
+color_helper!(saturate: |color: Color, amount: f64| {
+    color.saturate(amount)
+});
+color_helper!(desaturate: |color: Color, amount: f64| {
+    color.desaturate(amount)
+});
+color_helper!(set_lightness: |color: Color, lightness: f64| {
+    color.set_lightness(lightness)
+});
+color_helper!(set_hue: |color: Color, hue: f64| {
+    color.set_hue(hue)
+});
+color_helper!(complement: |color: Color| {
+    color.complement()
 });
-handlebars_helper!(darken: |color: String, weight: f32| {
-    HSLA::from_hex(&color)?.darken(Ratio::from_f32(weight)).to_hex()
+color_helper!(grayscale: |color: Color| {
+    color.grayscale()
 });
-handlebars_helper!(mix: |color_a: String, color_b: String, t: f32| {
-    HSLA::from_hex(&color_a)?.mix(HSLA::from_hex(&color_b)?, Ratio::from_f32(t)).to_hex()
+color_helper!(invert: |color: Color| {
+    color.invert()
 });
-handlebars_helper!(opacity: |color: String, amount: f32| {
-    HSLA::from_hex(&color)?.fade(Ratio::from_f32(amount)).to_hex()
+color_helper!(mix: |color_a: Color, color_b: Color, t: f64, {space: Space = Space::Srgb}| {
+    color_a.mix(color_b, t, space)
 });
-handlebars_helper!(rgb: |color: String| {
-    HSLA::from_hex(&color)?.to_rgb().to_string()
+color_helper!(gradient: |color_a: Color, color_b: Color, steps: u32, {space: Space = Space::Oklab}| {
+    color_a.gradient(color_b, steps, space)
 });
-handlebars_helper!(rgba: |color: String| {
-    HSLA::from_hex(&color)?.to_rgba().to_string()
+color_helper!(scale: |color: Color, steps: u32| {
+    color.scale(steps)
+});
+color_helper!(lighten_ok: |color: Color, amount: f64| {
+    color.lighten_ok(amount)
+});
+color_helper!(darken_ok: |color: Color, amount: f64| {
+    color.darken_ok(amount)
+});
+color_helper!(chroma: |color: Color, amount: f64| {
+    color.chroma(amount)
+});
+color_helper!(hue_rotate: |color: Color, degrees: f64| {
+    color.hue_rotate(degrees)
+});
+color_helper!(opacity: |color: Color, amount: f64| {
+    color.with_alpha(amount)
+});
+color_helper!(rgb: |color: Color| {
+    color.to_css_rgb()
+});
+color_helper!(rgba: |color: Color| {
+    color.to_css_rgba()
+});
+color_helper!(hsl: |color: Color| {
+    color.to_css_hsl()
+});
+color_helper!(hsla: |color: Color| {
+    color.to_css_hsla()
+});
+color_helper!(contrast: |color_a: Color, color_b: Color| {
+    color_a.contrast(color_b)
+});
+color_helper!(apca: |text: Color, background: Color| {
+    text.apca(background)
+});
+color_helper!(readable_on: |background: Color, *candidates: Color, {min: f64 = 4.5}| {
+    let readable = candidates.iter().find(|candidate| candidate.contrast(background) >= min);
+    // fall back to the most readable candidate, rather than failing the render
+    let best = candidates.iter().max_by(|a, b| {
+        a.contrast(background).total_cmp(&b.contrast(background))
+    });
+    match readable.or(best) {
+        Some(color) => *color,
+        None => return Err(RenderError::new(
+            "`readable_on` helper: Needs at least one candidate color",
+        )),
+    }
 });
-handlebars_helper!(hsl: |color: String| {
-    HSLA::from_hex(&color)?.to_hsl().to_string()
+color_helper!(oklab: |color: Color| {
+    color.to_css_oklab()
 });
-handlebars_helper!(hsla: |color: String| {
-    HSLA::from_hex(&color)?.to_string()
+color_helper!(oklch: |color: Color| {
+    color.to_css_oklch()
 });
-handlebars_helper!(red_i: |color: String| {
-    HSLA::from_hex(&color)?.to_rgba().r.as_u8()
+color_helper!(red_i: |color: Color| {
+    color.to_rgba8()[0]
 });
-handlebars_helper!(green_i: |color: String| {
-    HSLA::from_hex(&color)?.to_rgba().g.as_u8()
+color_helper!(green_i: |color: Color| {
+    color.to_rgba8()[1]
 });
-handlebars_helper!(blue_i: |color: String| {
-    HSLA::from_hex(&color)?.to_rgba().b.as_u8()
+color_helper!(blue_i: |color: Color| {
+    color.to_rgba8()[2]
 });
-handlebars_helper!(alpha_i: |color: String| {
-    HSLA::from_hex(&color)?.to_rgba().a.as_u8()
+color_helper!(alpha_i: |color: Color| {
+    color.to_rgba8()[3]
 });
-handlebars_helper!(red_f: |color: String| {
-    HSLA::from_hex(&color)?.to_rgba().r.as_f32()
+color_helper!(red_f: |color: Color| {
+    color.r
 });
-handlebars_helper!(green_f: |color: String| {
-    HSLA::from_hex(&color)?.to_rgba().g.as_f32()
+color_helper!(green_f: |color: Color| {
+    color.g
 });
-handlebars_helper!(blue_f: |color: String| {
-    HSLA::from_hex(&color)?.to_rgba().b.as_f32()
+color_helper!(blue_f: |color: Color| {
+    color.b
 });
-handlebars_helper!(alpha_f: |color: String| {
-    HSLA::from_hex(&color)?.to_rgba().a.as_f32()
+color_helper!(alpha_f: |color: Color| {
+    color.a
 });
 
 pub fn darklight(
     h: &Helper,
     _r: &Handlebars,
     ctx: &Context,
-    _rc: &mut RenderContext,
+    rc: &mut RenderContext,
     out: &mut dyn Output,
 ) -> HelperResult {
     let dark = h
@@ -83,7 +355,16 @@ pub fn darklight(
         .param(1)
         .ok_or_else(|| RenderError::new("Missing parameter `light` in position 1"))?;
 
-    if ctx.data()["flavor"] == "latte" {
+    // read from the current block first, so that it follows `{{#each flavors}}`
+    let is_light = |path| {
+        rc.evaluate(ctx, path)
+            .ok()
+            .and_then(|value| value.as_json().as_bool())
+    };
+    if is_light("isLight")
+        .or_else(|| is_light("@root.isLight"))
+        .unwrap_or(false)
+    {
         out.write(&light.render())?;
     } else {
         out.write(&dark.render())?;
@@ -1,8 +1,13 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::unwrap_used)]
 #![allow(clippy::cast_possible_truncation)] // we like truncating u32s into u8s around here
+pub mod audit;
+pub mod color;
+pub mod context;
+pub mod format;
 pub mod frontmatter;
 mod helper;
-mod parse;
+pub mod matrix;
+pub mod palette;
+pub mod parse;
 pub mod postprocess;
 pub mod template;
-
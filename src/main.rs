@@ -1,42 +1,35 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::unwrap_used)]
 #![allow(clippy::cast_possible_truncation)]
-use std::clone::Clone;
-
 // we like truncating u32s into u8s around here
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
 use clap::Parser;
-use clap_stdin::FileOrStdin;
 use color_eyre::{
     eyre::{eyre, Context},
     Result,
 };
+use handlebars::Handlebars;
 
+use whiskers::audit::ContrastPair;
+use whiskers::context;
+use whiskers::format::{self, ColorFormat};
 use whiskers::frontmatter;
+use whiskers::matrix::{self, Dimension};
+use whiskers::palette::{self, Flavor};
+use whiskers::parse;
 use whiskers::postprocess::postprocess;
 use whiskers::template::{self, helpers};
 
-#[derive(clap::ValueEnum, Clone, Debug)]
-enum Flavor {
-    Latte,
-    Frappe,
-    Macchiato,
-    Mocha,
-}
-
-impl From<Flavor> for catppuccin::Flavour {
-    fn from(value: Flavor) -> Self {
-        match value {
-            Flavor::Latte => Self::Latte,
-            Flavor::Frappe => Self::Frappe,
-            Flavor::Macchiato => Self::Macchiato,
-            Flavor::Mocha => Self::Mocha,
-        }
-    }
-}
-
 #[derive(Clone, Debug)]
 struct Override {
     pub key: String,
     pub value: serde_json::Value,
+    /// The value as written on the command line, kept for hex codes that
+    /// replace a color, which YAML could read as numbers.
+    pub raw: Option<String>,
 }
 
 fn parse_override(s: &str) -> Result<Override> {
@@ -44,71 +37,140 @@ fn parse_override(s: &str) -> Result<Override> {
     if let Some((key, value)) = kvpair {
         return Ok(Override {
             key: key.trim().to_string(),
-            value: serde_json::Value::String(value.trim().to_owned()),
+            value: parse_override_value(value.trim()),
+            raw: Some(value.trim().to_string()),
         });
     }
     Err(eyre!("invalid override, expected 'key=value', got '{}'", s))
 }
 
+/// Parse an override value as YAML, so that `true`, `0.8`, `120`, or `[a, b]`
+/// keep their types. Anything that isn't valid YAML is kept as a string, and
+/// quoting the value, as in `key="true"`, forces a string.
+///
+/// Hex codes such as `000000` that replace a color are kept as written by
+/// [`contextualize_overrides`] instead.
+fn parse_override_value(value: &str) -> serde_json::Value {
+    // YAML would read a leading `#` as a comment
+    if value.is_empty() || value.starts_with('#') {
+        return value.into();
+    }
+
+    serde_yaml::from_str(value).unwrap_or_else(|_| value.into())
+}
+
 #[derive(clap::Parser, Debug)]
 struct Args {
     /// Path to the template file to render, or `-` for stdin
     #[arg(required_unless_present = "list_helpers")]
-    template: Option<FileOrStdin>,
+    template: Option<PathBuf>,
+
+    /// Flavor to get colors from (latte, frappe, macchiato, or mocha, unless `--palette` is given), or `all` to make every flavor available under `flavors`. Optional if the template's `whiskers.matrix` includes `flavor` or the palette has a single flavor
+    flavor: Option<String>,
+
+    /// Load colors from a JSON, YAML, or TOML palette file instead of the Catppuccin palette
+    #[arg(long, value_name = "PATH")]
+    palette: Option<PathBuf>,
 
-    /// Flavor to get colors from
-    #[arg(value_enum, required_unless_present = "list_helpers")]
-    flavor: Option<Flavor>,
+    /// Accent color to set as `accent` (its hex code) and `accentName`: rosewater, flamingo, pink, mauve, red, maroon, peach, yellow, green, teal, sky, sapphire, blue, or lavender, unless `--palette` is given. Restricts the `accent` dimension of a `whiskers.matrix`, which sets `accent` to the name instead
+    #[arg(long, value_name = "COLOR")]
+    accent: Option<String>,
 
-    /// The overrides to apply to the template in key=value format
+    /// How to write colors in the output: hex (f38ba8), hash (#f38ba8), upper (F38BA8), 0x (0xF38BA8), rgb (rgb(243, 139, 168)), hsl (hsl(343, 81%, 75%)), or float (0.953, 0.545, 0.659, 1.000). Overrides any `whiskers.colorFormat` in the frontmatter
+    #[arg(long, value_name = "FORMAT")]
+    color_format: Option<ColorFormat>,
+
+    /// The overrides to apply to the template in key=value format. Keys can be dot-separated paths such as `colors.selection`. Values are parsed as YAML, so quote them to force a string
     #[arg(long("override"), value_parser(parse_override))]
     overrides: Vec<Override>,
 
+    /// Write the result to this path instead of stdout. The path is rendered as a template, overriding any `whiskers.filename` in the frontmatter
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Instead of writing the result, compare it against the file on disk and print a diff if they differ. Uses the output filename unless a path is given
+    #[arg(long, value_name = "PATH", num_args = 0..=1, require_equals = true)]
+    #[allow(clippy::option_option)] // clap's representation of a flag with an optional value
+    check: Option<Option<PathBuf>>,
+
+    /// Instead of writing the result, check the contrast of each `whiskers.contrast` pair in the frontmatter for every flavor, printing a table of ratios. Fails if any pair is below its minimum
+    #[arg(long, conflicts_with = "check")]
+    audit: bool,
+
+    /// Keep running and render again whenever the template changes, printing errors instead of exiting
+    #[arg(short, long)]
+    watch: bool,
+
     /// List all template helpers in markdown format
     #[arg(short, long)]
     list_helpers: bool,
 }
 
-fn contextualize_overrides(overrides: Vec<Override>, ctx: &serde_json::Value) -> Vec<Override> {
-    let map = ctx.as_object().expect("base context is an object value");
+/// Apply the overrides to the context in order. Values that name a context
+/// variable, such as `sky`, are replaced with that variable's current value,
+/// and hex codes replacing a color, such as `base=000000`, stay strings.
+///
+/// Returns the resolved overrides, so they can be applied again later without
+/// resolving them a second time.
+fn contextualize_overrides(
+    ctx: &mut serde_json::Value,
+    overrides: &[Override],
+    replace: &[String],
+) -> Result<Vec<Override>> {
     overrides
-        .into_iter()
+        .iter()
         .map(|o| {
-            let lookup = o.value.as_str().expect("override values are strings");
-            let value = map.get(lookup).map(Clone::clone).unwrap_or(o.value);
-            Override { key: o.key, value }
+            let current = ctx
+                .pointer(&format!("/{}", o.key.replace('.', "/")))
+                .and_then(serde_json::Value::as_str);
+            let replaces_color = current.is_some_and(|current| parse::parse_color(current).is_ok());
+            let hex = o
+                .raw
+                .as_deref()
+                .filter(|_| replaces_color)
+                .and_then(|raw| Some((raw, parse::normalize_hex(raw).ok()?)));
+
+            let value = match hex {
+                // a hex code replacing a marked palette color is marked like it
+                Some((_, hex)) if current.and_then(format::unmark).is_some() => {
+                    format::mark(&hex).into()
+                }
+                Some((raw, _)) => raw.into(),
+                None => o
+                    .value
+                    .as_str()
+                    .and_then(|lookup| ctx.get(lookup))
+                    .cloned()
+                    .unwrap_or_else(|| o.value.clone()),
+            };
+            let o = Override {
+                key: o.key.clone(),
+                value,
+                raw: o.raw.clone(),
+            };
+            apply_override(ctx, &o, replace)?;
+            Ok(o)
         })
         .collect()
 }
 
-fn overrides_to_map(overrides: Vec<Override>) -> serde_json::Map<String, serde_json::Value> {
-    overrides.into_iter().map(|o| (o.key, o.value)).collect()
-}
-
-fn merge_contexts(
-    ctx: serde_json::Value,
-    frontmatter: Option<serde_json::Value>,
-    overrides: Vec<Override>,
-) -> serde_json::Value {
-    type Map = serde_json::Map<String, serde_json::Value>;
-    let mut merged = Map::new();
-
-    let overrides = contextualize_overrides(overrides, &ctx);
-
-    merged.extend(
-        serde_json::from_value::<Map>(ctx).expect("base context is deserializable into a map"),
-    );
+/// Apply an override, updating the `colors`, `palette`, `accents`, and
+/// `neutrals` entries of a palette color along with the color itself.
+fn apply_override(ctx: &mut serde_json::Value, o: &Override, replace: &[String]) -> Result<()> {
+    context::merge_path(ctx, &o.key, o.value.clone(), replace)
+        .wrap_err_with(|| format!("Failed to apply override `{}`", o.key))?;
 
-    if let Some(frontmatter) = frontmatter {
-        merged.extend(
-            serde_json::from_value::<Map>(frontmatter)
-                .expect("frontmatter is deserializable into a map"),
-        );
+    let (flavor, name) = match o.key.rsplit_once('.') {
+        Some((parent, name)) => (
+            ctx.pointer_mut(&format!("/{}", parent.replace('.', "/"))),
+            name,
+        ),
+        None => (Some(ctx), o.key.as_str()),
+    };
+    if let Some(flavor) = flavor {
+        template::update_color(flavor, name);
     }
-
-    merged.extend(overrides_to_map(overrides));
-
-    serde_json::Value::Object(merged)
+    Ok(())
 }
 
 fn main() -> Result<()> {
@@ -124,25 +186,586 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    let template = &args
+    let template_path = args
         .template
+        .as_deref()
         .expect("template_path is guaranteed to be set");
 
-    let flavor = args.flavor.expect("flavor is guaranteed to be set");
+    if args.watch {
+        return watch(&args, template_path);
+    }
+
+    run(&args, &read_template(template_path)?)
+}
+
+fn read_template(path: &Path) -> Result<String> {
+    if path == Path::new("-") {
+        let mut template = String::new();
+        std::io::stdin()
+            .read_to_string(&mut template)
+            .wrap_err("Failed to read template from stdin")?;
+        return Ok(template);
+    }
+
+    std::fs::read_to_string(path).wrap_err_with(|| format!("Failed to read {}", path.display()))
+}
+
+fn watch(args: &Args, template_path: &Path) -> Result<()> {
+    if template_path == Path::new("-") {
+        return Err(eyre!("`--watch` needs a template file, not stdin"));
+    }
+
+    let files = std::iter::once(template_path)
+        .chain(args.palette.as_deref())
+        .map(|path| {
+            path.canonicalize()
+                .wrap_err_with(|| format!("Failed to find {}", path.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // watch the containing directories rather than the files themselves, so
+    // that editors which save by replacing the file don't end the watch.
+    let (tx, rx) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).wrap_err("Failed to start watching for changes")?;
+    for path in &files {
+        let dir = path.parent().unwrap_or(path);
+        notify::Watcher::watch(&mut watcher, dir, notify::RecursiveMode::NonRecursive)
+            .wrap_err_with(|| format!("Failed to watch {}", dir.display()))?;
+    }
+
+    let render = || {
+        if let Err(e) = read_template(template_path).and_then(|template| run(args, &template)) {
+            eprintln!("{e:?}");
+        }
+    };
+
+    render();
+    for event in &rx {
+        let Ok(event) = event else { continue };
+        if event.kind.is_access() || !event.paths.iter().any(|p| files.contains(p)) {
+            continue;
+        }
+
+        // a single save often produces a burst of events, so wait for it to settle
+        while rx.recv_timeout(Duration::from_millis(50)).is_ok() {}
+        render();
+    }
+
+    Ok(())
+}
+
+fn run(args: &Args, template: &str) -> Result<()> {
+    let config = frontmatter::config(template).wrap_err("Invalid frontmatter")?;
+    let filename = args.output.clone().or(config.filename);
+
+    let palette = load_palette(args)?;
+
+    let renderer = Renderer {
+        reg: template::make_registry(),
+        format: args
+            .color_format
+            .or(config.color_format)
+            .unwrap_or_default(),
+        palette: &palette,
+        template,
+        overrides: &args.overrides,
+        replace: &config.replace,
+    };
+
+    if args.audit {
+        let combinations = match config.matrix.as_deref() {
+            Some(matrix) => matrix_combinations(args, &palette, matrix, true)?,
+            None => audit_flavors(args, &palette)?
+                .into_iter()
+                .map(|flavor| Combination {
+                    flavor: Some(flavor),
+                    accent: args.accent.clone(),
+                    vars: serde_json::Map::new(),
+                })
+                .collect(),
+        };
+        return audit(&renderer, &config.contrast, combinations);
+    }
+
+    let Some(matrix) = config.matrix else {
+        let flavor = selected_flavor(args, &palette)?;
+        let (result, ctx) =
+            renderer.render(flavor, args.accent.as_deref(), serde_json::Map::new())?;
+
+        let path = match (args.check.clone().flatten(), filename) {
+            (Some(path), _) => Some(path),
+            (None, Some(filename)) => Some(renderer.output_path(&filename, &ctx)?),
+            (None, None) => None,
+        };
+
+        if args.check.is_some() {
+            let path = path.ok_or_else(|| {
+                eyre!("Nothing to check against, pass `--check=<PATH>`, pass `--output`, or set `whiskers.filename`")
+            })?;
+            return report_check(&[check_output(&path, &result)?]);
+        }
+
+        if let Some(path) = path {
+            write_output(&path, &result)?;
+        } else {
+            println!("{result}");
+        }
+        return Ok(());
+    };
+
+    let filename = filename.ok_or_else(|| {
+        eyre!("Templates with a `whiskers.matrix` need an output filename, set `whiskers.filename` or pass `--output`")
+    })?;
+
+    if matches!(args.check, Some(Some(_))) {
+        return Err(eyre!(
+            "`--check` cannot take a path for templates with a `whiskers.matrix`, the output filename is used instead"
+        ));
+    }
+
+    // everything is rendered before writing, so that combinations sharing an
+    // output path are caught before any of them overwrite each other.
+    let mut outputs: Vec<(PathBuf, String)> = vec![];
+    let mut labels: Vec<String> = vec![];
+    for combination in matrix_combinations(args, &palette, &matrix, false)? {
+        let label = combination.label();
+        let Combination {
+            flavor,
+            accent,
+            vars,
+        } = combination;
+        let (result, ctx) = renderer.render(flavor, accent.as_deref(), vars)?;
+        let path = renderer.output_path(&filename, &ctx)?;
+        if let Some(i) = outputs.iter().position(|(other, _)| *other == path) {
+            return Err(eyre!(
+                "Matrix combinations {} and {label} both render to {}, include every dimension in the output filename",
+                labels[i],
+                path.display()
+            ));
+        }
+        outputs.push((path, result));
+        labels.push(label);
+    }
+
+    let mut checks = vec![];
+    for (path, result) in outputs {
+        if args.check.is_some() {
+            checks.push(check_output(&path, &result)?);
+        } else {
+            write_output(&path, &result)?;
+        }
+    }
+
+    if args.check.is_some() {
+        return report_check(&checks);
+    }
+
+    Ok(())
+}
+
+/// A flavor, accent, and the rest of the matrix values to render the template with.
+struct Combination<'a> {
+    flavor: Option<&'a Flavor>,
+    accent: Option<String>,
+    vars: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Combination<'_> {
+    /// A short description such as `mocha/blue`, for reports.
+    fn label(&self) -> String {
+        let flavor = self.flavor.map_or("all", |f| f.name.as_str());
+        std::iter::once(flavor.to_string())
+            .chain(self.accent.clone())
+            .chain(
+                self.vars
+                    .iter()
+                    .filter(|(key, _)| *key != "accentName")
+                    .map(|(_, value)| match value {
+                        serde_json::Value::String(s) => s.clone(),
+                        value => value.to_string(),
+                    }),
+            )
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+/// The combinations of the template's `whiskers.matrix` to render, skipping
+/// those excluded by the flavor or `--accent` given on the command line.
+///
+/// When auditing a matrix without a `flavor` dimension, each combination is
+/// repeated for every flavor in [`audit_flavors`], rather than needing a
+/// flavor on the command line.
+fn matrix_combinations<'a>(
+    args: &Args,
+    palette: &'a [Flavor],
+    matrix: &[Dimension],
+    audit: bool,
+) -> Result<Vec<Combination<'a>>> {
+    let only_flavor = match args.flavor.as_deref() {
+        None | Some("all") => None,
+        Some(name) => Some(find_flavor(palette, name)?),
+    };
+
+    let mut combinations = vec![];
+    for mut vars in matrix::combinations(matrix, palette)? {
+        let flavors = match vars.remove("flavor") {
+            Some(name) => {
+                let flavor = palette
+                    .iter()
+                    .find(|f| Some(f.name.as_str()) == name.as_str())
+                    .ok_or_else(|| eyre!("Unknown flavor {name} in `whiskers.matrix`"))?;
+                if only_flavor.is_some_and(|only| only != flavor) {
+                    continue;
+                }
+                vec![Some(flavor)]
+            }
+            None if audit => audit_flavors(args, palette)?
+                .into_iter()
+                .map(Some)
+                .collect(),
+            None => vec![selected_flavor(args, palette)?],
+        };
+
+        // an `accent` dimension is set as is, to the accent's name, rather
+        // than selecting the accent like `--accent` does, so that templates
+        // using it in filenames or with `lookup` keep working. `accentName`
+        // is set to the name either way.
+        let accent = match vars.get("accent").cloned() {
+            Some(name) => {
+                if args
+                    .accent
+                    .as_ref()
+                    .is_some_and(|only| name.as_str() != Some(only.as_str()))
+                {
+                    continue;
+                }
+                vars.insert("accentName".to_string(), name);
+                None
+            }
+            None => args.accent.clone(),
+        };
+
+        combinations.extend(flavors.into_iter().map(|flavor| Combination {
+            flavor,
+            accent: accent.clone(),
+            vars: vars.clone(),
+        }));
+    }
+    Ok(combinations)
+}
+
+/// The flavors to audit a template without a `whiskers.matrix` in: the one
+/// given on the command line, or every flavor of the palette.
+fn audit_flavors<'a>(args: &Args, palette: &'a [Flavor]) -> Result<Vec<&'a Flavor>> {
+    match args.flavor.as_deref() {
+        None | Some("all") => Ok(palette.iter().collect()),
+        Some(name) => find_flavor(palette, name).map(|flavor| vec![flavor]),
+    }
+}
+
+/// Load the palette given with `--palette`, or the Catppuccin palette, and
+/// check that it has the accent given with `--accent`.
+fn load_palette(args: &Args) -> Result<Vec<Flavor>> {
+    let palette = match &args.palette {
+        Some(path) => palette::load(path)
+            .wrap_err_with(|| format!("Failed to load palette {}", path.display()))?,
+        None => palette::catppuccin(),
+    };
+
+    if let Some(accent) = &args.accent {
+        let accents = palette_accents(&palette);
+        if !accents.contains(accent) {
+            return Err(eyre!(
+                "Unknown accent `{accent}`, expected one of: {}",
+                accents.join(", ")
+            ));
+        }
+    }
+
+    Ok(palette)
+}
+
+/// The flavor chosen on the command line, or `None` for `all`. A palette
+/// with a single flavor doesn't need one to be chosen.
+fn selected_flavor<'a>(args: &Args, palette: &'a [Flavor]) -> Result<Option<&'a Flavor>> {
+    match (args.flavor.as_deref(), palette) {
+        (Some("all"), _) => Ok(None),
+        (Some(name), _) => find_flavor(palette, name).map(Some),
+        (None, [flavor]) => Ok(Some(flavor)),
+        (None, _) => Err(eyre!(
+            "No flavor given. Pass a flavor, or add `flavor` to the template's `whiskers.matrix`"
+        )),
+    }
+}
+
+/// The names of the accents across every flavor of the palette, in palette order.
+fn palette_accents(palette: &[Flavor]) -> Vec<String> {
+    let mut accents: Vec<String> = vec![];
+    for accent in palette.iter().flat_map(|f| &f.accents) {
+        if !accents.contains(accent) {
+            accents.push(accent.clone());
+        }
+    }
+    accents
+}
+
+/// Look up the accent in the flavor, as overrides setting `accent` to its hex
+/// code and `accentName` to its name. Without a flavor, only `accentName` is
+/// set, and each flavor's `accent` is set under `flavors`.
+///
+/// The hex code is taken from the flavor's entry in `ctx`, so that it keeps
+/// any [`format::mark`](whiskers::format::mark) on the color.
+fn accent_overrides(
+    ctx: &mut serde_json::Value,
+    palette: &[Flavor],
+    flavor: Option<&Flavor>,
+    name: &str,
+) -> Result<Vec<Override>> {
+    let hex = |ctx: &serde_json::Value, flavor: &Flavor| {
+        if flavor.accents.iter().any(|accent| accent == name) {
+            ctx["flavors"][&flavor.name].get(name).cloned()
+        } else {
+            None
+        }
+    };
+
+    for f in palette {
+        if let Some(hex) = hex(ctx, f) {
+            ctx["flavors"][&f.name]["accent"] = hex;
+            ctx["flavors"][&f.name]["accentName"] = name.into();
+        }
+    }
+
+    let mut overrides = vec![];
+    if let Some(flavor) = flavor {
+        let value = hex(ctx, flavor)
+            .ok_or_else(|| eyre!("Unknown accent `{name}` for flavor `{}`", flavor.name))?;
+        overrides.push(Override {
+            key: "accent".to_string(),
+            value,
+            raw: None,
+        });
+    }
+    overrides.push(Override {
+        key: "accentName".to_string(),
+        value: name.into(),
+        raw: None,
+    });
+    Ok(overrides)
+}
+
+fn find_flavor<'a>(palette: &'a [Flavor], name: &str) -> Result<&'a Flavor> {
+    palette
+        .iter()
+        .find(|f| f.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            let names: Vec<&str> = palette.iter().map(|f| f.name.as_str()).collect();
+            eyre!(
+                "Unknown flavor `{name}`, expected one of: {}, all",
+                names.join(", ")
+            )
+        })
+}
+
+/// The parts of a render that stay the same across flavors and matrix combinations.
+struct Renderer<'a> {
+    reg: Handlebars<'static>,
+    /// The format colors from the palette and color helpers are written in.
+    format: ColorFormat,
+    palette: &'a [Flavor],
+    template: &'a str,
+    overrides: &'a [Override],
+    replace: &'a [String],
+}
+
+impl Renderer<'_> {
+    fn render(
+        &self,
+        flavor: Option<&Flavor>,
+        accent: Option<&str>,
+        vars: serde_json::Map<String, serde_json::Value>,
+    ) -> Result<(String, serde_json::Value)> {
+        let mut ctx = template::make_context(self.palette, flavor);
+        // colors are marked, rather than formatted here, so that helpers and
+        // the frontmatter still read them as colors.
+        if self.format != ColorFormat::Hex {
+            template::mark_colors(&mut ctx);
+        }
+
+        // matrix values and the accent are treated like overrides, ahead of
+        // those passed on the command line, so that frontmatter defaults such
+        // as `accent: '{{mauve}}'` don't replace them.
+        let mut overrides: Vec<Override> = vars
+            .into_iter()
+            .map(|(key, value)| Override {
+                key,
+                value,
+                raw: None,
+            })
+            .collect();
+        if let Some(name) = accent {
+            overrides.extend(accent_overrides(&mut ctx, self.palette, flavor, name)?);
+        }
+        for o in &overrides {
+            apply_override(&mut ctx, o, self.replace)?;
+        }
+
+        // overrides are applied before rendering the frontmatter, so that values
+        // derived from an overridden color pick up the change, and again after
+        // merging the frontmatter so that they take precedence over it.
+        overrides.extend(contextualize_overrides(
+            &mut ctx,
+            self.overrides,
+            self.replace,
+        )?);
+
+        let overridden: Vec<String> = overrides.iter().map(|o| o.key.clone()).collect();
+        let (content, frontmatter) = frontmatter::render_and_parse(
+            self.template,
+            &self.reg,
+            &ctx,
+            &overridden,
+            self.replace,
+        )
+        .wrap_err("Failed to render frontmatter")?;
+
+        if let Some(frontmatter) = frontmatter {
+            context::merge(&mut ctx, frontmatter, self.replace);
+        }
+
+        for o in &overrides {
+            apply_override(&mut ctx, o, self.replace)?;
+        }
+
+        let result = self
+            .reg
+            .render_template(content, &ctx)
+            .wrap_err("Failed to render template")?;
+        Ok((self.format.apply(&postprocess(&result)), ctx))
+    }
+
+    fn output_path(&self, filename: &str, ctx: &serde_json::Value) -> Result<PathBuf> {
+        let path = self
+            .reg
+            .render_template(filename, ctx)
+            .wrap_err("Failed to render output filename")?;
+        Ok(PathBuf::from(ColorFormat::Hex.apply(&path)))
+    }
+}
 
-    let reg = template::make_registry();
+fn write_output(path: &Path, result: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .wrap_err_with(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+    std::fs::write(path, format!("{result}\n"))
+        .wrap_err_with(|| format!("Failed to write {}", path.display()))
+}
 
-    let ctx = template::make_context(flavor.into());
-    let (content, frontmatter) = frontmatter::render_and_parse(template, &reg, &ctx);
+/// Compare the result against the file at `path`, printing a diff if they
+/// differ. Returns whether the file is up to date.
+fn check_output(path: &Path, result: &str) -> Result<bool> {
+    let expected = format!("{result}\n");
+    let actual = match std::fs::read_to_string(path) {
+        Ok(actual) => actual,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            println!("{} does not exist", path.display());
+            return Ok(false);
+        }
+        Err(e) => return Err(e).wrap_err_with(|| format!("Failed to read {}", path.display())),
+    };
 
-    let ctx = merge_contexts(ctx, frontmatter, args.overrides);
+    if actual == expected {
+        return Ok(true);
+    }
 
-    let result = reg
-        .render_template(content, &ctx)
-        .wrap_err("Failed to render template")?;
-    let result = postprocess(&result);
-    println!("{result}");
+    let name = path.display().to_string();
+    print!(
+        "{}",
+        similar::TextDiff::from_lines(&actual, &expected)
+            .unified_diff()
+            .header(&name, &format!("{name} (rendered)"))
+    );
+    Ok(false)
+}
 
+fn report_check(checks: &[bool]) -> Result<()> {
+    let outdated = checks.iter().filter(|&&ok| !ok).count();
+    if outdated > 0 {
+        return Err(eyre!(
+            "{outdated} of {} file(s) are out of date",
+            checks.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Measure every contrast pair in each combination, printing a table of the
+/// results. Fails if any pair is below its minimum.
+fn audit(
+    renderer: &Renderer,
+    pairs: &[ContrastPair],
+    combinations: Vec<Combination>,
+) -> Result<()> {
+    if pairs.is_empty() {
+        return Err(eyre!(
+            "Nothing to audit, add `fg` and `bg` pairs to `whiskers.contrast` in the frontmatter"
+        ));
+    }
+
+    let mut rows = vec![["flavor", "fg", "bg", "ratio", "min", "result"].map(String::from)];
+    let mut failed = 0;
+    for combination in combinations {
+        let label = combination.label();
+        let (_, ctx) = renderer.render(
+            combination.flavor,
+            combination.accent.as_deref(),
+            combination.vars,
+        )?;
+        for pair in pairs {
+            let measurement = pair
+                .measure(&renderer.reg, &ctx)
+                .wrap_err_with(|| format!("Failed to audit {label}"))?;
+            let passed = measurement.ratio >= pair.min;
+            if !passed {
+                failed += 1;
+            }
+            rows.push([
+                label.clone(),
+                format!("{} ({})", pair.fg, measurement.fg),
+                format!("{} ({})", pair.bg, measurement.bg),
+                format!("{:.2}", measurement.ratio),
+                pair.min.to_string(),
+                if passed { "ok" } else { "FAIL" }.to_string(),
+            ]);
+        }
+    }
+
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    for row in &rows {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+
+    if failed > 0 {
+        return Err(eyre!(
+            "{failed} of {} contrast pair(s) are below their minimum",
+            rows.len() - 1
+        ));
+    }
     Ok(())
 }
 
@@ -159,3 +782,301 @@ fn list_helpers() {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn renderer<'a>(
+        palette: &'a [Flavor],
+        template: &'a str,
+        overrides: &'a [Override],
+    ) -> Renderer<'a> {
+        Renderer {
+            reg: template::make_registry(),
+            format: ColorFormat::Hex,
+            palette,
+            template,
+            overrides,
+            replace: &[],
+        }
+    }
+
+    /// An empty directory for a test to write files to.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("whiskers-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("scratch directory is created");
+        dir
+    }
+
+    #[test]
+    fn check_files() {
+        let dir = scratch_dir("check");
+        let path = dir.join("out.txt");
+
+        assert!(!check_output(&path, "a").expect("missing file is checked"));
+
+        write_output(&path, "a\nb").expect("file is written");
+        assert!(check_output(&path, "a\nb").expect("file is checked"));
+        assert!(!check_output(&path, "a\nc").expect("file is checked"));
+
+        assert!(report_check(&[true, true]).is_ok());
+        let error = report_check(&[true, false, true]).expect_err("a file is out of date");
+        assert_eq!(error.to_string(), "1 of 3 file(s) are out of date");
+        std::fs::remove_dir_all(dir).expect("scratch directory is removed");
+    }
+
+    #[test]
+    fn check_matrix() {
+        let dir = scratch_dir("check-matrix");
+        let template = format!(
+            "---\nwhiskers:\n  matrix: [flavor]\n  filename: \"{}/{{{{flavor}}}}.txt\"\n---\n{{{{base}}}}",
+            dir.display()
+        );
+        let write = Args::parse_from(["whiskers", "-"]);
+        let check = Args::parse_from(["whiskers", "-", "--check"]);
+
+        run(&write, &template).expect("files are written");
+        assert!(run(&check, &template).is_ok());
+
+        std::fs::write(dir.join("frappe.txt"), "stale\n").expect("file is written");
+        let error = run(&check, &template).expect_err("a file is out of date");
+        assert_eq!(error.to_string(), "1 of 4 file(s) are out of date");
+
+        std::fs::remove_file(dir.join("mocha.txt")).expect("file is removed");
+        let error = run(&check, &template).expect_err("files are out of date");
+        assert_eq!(error.to_string(), "2 of 4 file(s) are out of date");
+        std::fs::remove_dir_all(dir).expect("scratch directory is removed");
+    }
+
+    #[test]
+    fn accent_overrides_frontmatter_default() {
+        let palette = palette::catppuccin();
+        let template =
+            "---\naccent: \"{{mauve}}\"\ndim: \"{{darken accent 0}}\"\n---\n{{accent}} {{dim}}";
+        let renderer = renderer(&palette, template, &[]);
+        let render = |accent| {
+            renderer
+                .render(Some(&palette[3]), accent, serde_json::Map::new())
+                .expect("template renders")
+                .0
+        };
+
+        assert_eq!(render(None), "cba6f7 cba6f7");
+        assert_eq!(render(Some("red")), "f38ba8 f38ba8");
+    }
+
+    #[test]
+    fn accent_in_every_flavor() {
+        let palette = palette::catppuccin();
+        let renderer = renderer(
+            &palette,
+            "{{#each flavors}}{{accentName}}={{accent}} {{/each}}{{accentName}}",
+            &[],
+        );
+        let (result, _) = renderer
+            .render(None, Some("red"), serde_json::Map::new())
+            .expect("template renders");
+        assert_eq!(result, "red=d20f39 red=e78284 red=ed8796 red=f38ba8 red");
+    }
+
+    #[test]
+    fn accent_missing_from_a_flavor() {
+        let palette = palette::parse(
+            r#"
+                [flavors.day]
+                accents = ["red", "blue"]
+                colors = { red = "ff0000", blue = "0000ff" }
+
+                [flavors.night]
+                accents = ["red"]
+                colors = { red = "ff0000", blue = "0000ff" }
+            "#,
+            "toml",
+            "brand",
+        )
+        .expect("valid palette");
+        let single = renderer(&palette, "{{accent}}", &[]);
+
+        let (result, _) = single
+            .render(Some(&palette[0]), Some("blue"), serde_json::Map::new())
+            .expect("day has the accent");
+        assert_eq!(result, "0000ff");
+
+        let error = single
+            .render(Some(&palette[1]), Some("blue"), serde_json::Map::new())
+            .expect_err("night lacks the accent");
+        assert_eq!(
+            error.to_string(),
+            "Unknown accent `blue` for flavor `night`"
+        );
+
+        let (result, ctx) = renderer(&palette, "{{flavors.day.accent}}", &[])
+            .render(None, Some("blue"), serde_json::Map::new())
+            .expect("all flavors render");
+        assert_eq!(result, "0000ff");
+        assert_eq!(ctx["flavors"]["night"].get("accent"), None);
+        assert_eq!(ctx["flavors"]["night"].get("accentName"), None);
+    }
+
+    #[test]
+    fn matrix_accents_set_accent_name() {
+        let palette = palette::catppuccin();
+        let matrix: Vec<Dimension> =
+            serde_yaml::from_str("[flavor, accent]").expect("valid matrix");
+
+        for (cli, expected) in [(vec![], 56), (vec!["--accent", "red"], 4)] {
+            let args = Args::parse_from(["whiskers", "template"].into_iter().chain(cli));
+            let combinations =
+                matrix_combinations(&args, &palette, &matrix, false).expect("valid matrix");
+            assert_eq!(combinations.len(), expected);
+            for combination in &combinations {
+                assert!(combination.accent.is_none());
+                assert_eq!(combination.vars["accentName"], combination.vars["accent"]);
+            }
+        }
+
+        let args = Args::parse_from(["whiskers", "template", "--accent", "red"]);
+        let combinations =
+            matrix_combinations(&args, &palette, &matrix, false).expect("valid matrix");
+        assert_eq!(combinations[0].label(), "latte/red");
+        let renderer = renderer(&palette, "{{accent}} {{accentName}}", &[]);
+        let Combination {
+            flavor,
+            accent,
+            vars,
+        } = combinations.into_iter().next().expect("a combination");
+        let (result, _) = renderer
+            .render(flavor, accent.as_deref(), vars)
+            .expect("template renders");
+        assert_eq!(result, "red red");
+    }
+
+    #[test]
+    fn overrides_apply_before_and_after_frontmatter() {
+        let palette = palette::catppuccin();
+        let template =
+            "---\nbg: \"{{lighten base 0.1}}\"\nfg: \"{{text}}\"\n---\n{{base}} {{bg}} {{fg}}";
+        let overrides = [
+            parse_override("base=000000").expect("valid override"),
+            parse_override("fg=ffffff").expect("valid override"),
+        ];
+
+        let (result, _) = renderer(&palette, template, &[])
+            .render(Some(&palette[3]), None, serde_json::Map::new())
+            .expect("template renders");
+        assert_eq!(result, "1e1e2e 32324d cdd6f4");
+
+        // `bg` is derived from the overridden `base`, and `fg` takes the
+        // override over the frontmatter
+        let (result, _) = renderer(&palette, template, &overrides)
+            .render(Some(&palette[3]), None, serde_json::Map::new())
+            .expect("template renders");
+        assert_eq!(result, "000000 1a1a1a ffffff");
+    }
+
+    #[test]
+    fn matrix_values_replace_frontmatter_defaults() {
+        let palette = palette::catppuccin();
+        let template = "---\nvariant: plain\nlabel: \"{{variant}}-{{flavor}}\"\n---\n{{label}}";
+        let renderer = renderer(&palette, template, &[]);
+        let mut vars = serde_json::Map::new();
+        vars.insert("variant".to_string(), "bold".into());
+
+        let (result, ctx) = renderer
+            .render(Some(&palette[3]), None, vars)
+            .expect("template renders");
+        assert_eq!(result, "bold-mocha");
+        assert_eq!(ctx["variant"], serde_json::json!("bold"));
+    }
+
+    #[test]
+    fn typed_overrides() {
+        assert_eq!(parse_override_value("true"), serde_json::json!(true));
+        assert_eq!(parse_override_value("0.8"), serde_json::json!(0.8));
+        assert_eq!(
+            parse_override_value("[a, b]"),
+            serde_json::json!(["a", "b"])
+        );
+        assert_eq!(parse_override_value("\"true\""), serde_json::json!("true"));
+        assert_eq!(parse_override_value("'0.8'"), serde_json::json!("0.8"));
+        assert_eq!(parse_override_value(""), serde_json::json!(""));
+        assert_eq!(parse_override_value("a: [b"), serde_json::json!("a: [b"));
+    }
+
+    #[test]
+    fn palette_name_overrides() {
+        for value in ["sky", "mauve", "surface0"] {
+            assert_eq!(parse_override_value(value), serde_json::json!(value));
+        }
+
+        let mut ctx =
+            template::make_context(&palette::catppuccin(), Some(&palette::catppuccin()[3]));
+        let resolved = contextualize_overrides(
+            &mut ctx,
+            &[parse_override("accent=sky").expect("valid override")],
+            &[],
+        )
+        .expect("override applies");
+        assert_eq!(resolved[0].value, serde_json::json!("89dceb"));
+        assert_eq!(ctx["accent"], serde_json::json!("89dceb"));
+    }
+
+    #[test]
+    fn overrides_update_color_objects() {
+        let mut ctx =
+            template::make_context(&palette::catppuccin(), Some(&palette::catppuccin()[3]));
+        contextualize_overrides(
+            &mut ctx,
+            &[
+                parse_override("base=000000").expect("valid override"),
+                parse_override("flavors.latte.red=ffffff").expect("valid override"),
+            ],
+            &[],
+        )
+        .expect("overrides apply");
+
+        assert_eq!(ctx["base"], serde_json::json!("000000"));
+        assert_eq!(ctx["colors"]["base"]["hex"], serde_json::json!("000000"));
+        assert_eq!(
+            ctx["colors"]["base"]["rgb"],
+            serde_json::json!({ "r": 0, "g": 0, "b": 0 })
+        );
+        let entry = |list: &str, name: &str| {
+            ctx[list]
+                .as_array()
+                .and_then(|colors| colors.iter().find(|color| color["name"] == name))
+                .map(|color| color["hex"].clone())
+        };
+        assert_eq!(entry("palette", "base"), Some(serde_json::json!("000000")));
+        assert_eq!(entry("neutrals", "base"), Some(serde_json::json!("000000")));
+        assert_eq!(entry("accents", "base"), None);
+
+        let latte = &ctx["flavors"]["latte"];
+        assert_eq!(latte["colors"]["red"]["hex"], serde_json::json!("ffffff"));
+        assert_eq!(latte["accents"][4]["hex"], serde_json::json!("ffffff"));
+        assert_eq!(ctx["colors"]["red"]["hex"], serde_json::json!("f38ba8"));
+    }
+
+    #[test]
+    fn hex_overrides_replacing_colors_are_strings() {
+        assert_eq!(parse_override_value("120"), serde_json::json!(120));
+        assert_eq!(parse_override_value("#fff"), serde_json::json!("#fff"));
+        assert_eq!(parse_override_value("1e1e2e"), serde_json::json!("1e1e2e"));
+
+        for value in [
+            "000000", "0xf38ba8", "0XF38BA8", "#f38ba8", "#fff", "fff", "1234", "1e1e2e",
+            "f38ba880",
+        ] {
+            let mut ctx =
+                template::make_context(&palette::catppuccin(), Some(&palette::catppuccin()[3]));
+            let overrides = [
+                parse_override(&format!("base={value}")).expect("valid override"),
+                parse_override("size=120").expect("valid override"),
+            ];
+            contextualize_overrides(&mut ctx, &overrides, &[]).expect("overrides apply");
+            assert_eq!(ctx["base"], serde_json::json!(value));
+            assert_eq!(ctx["size"], serde_json::json!(120));
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::palette::Palette;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("unknown matrix dimension `{0}`, expected `flavor`, `accent`, or a list of values")]
+    UnknownDimension(String),
+
+    #[error("matrix dimensions must have exactly one name, got {0:?}")]
+    InvalidDimension(Vec<String>),
+
+    #[error("matrix dimension `{0}` has no values")]
+    EmptyDimension(String),
+}
+
+/// One dimension of a `whiskers.matrix` frontmatter block.
+///
+/// A dimension is either the name of a built-in dimension (`flavor` or
+/// `accent`), which iterates over every known value, or a single-key map from
+/// a name to an explicit list of values.
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Dimension {
+    Builtin(String),
+    Values(BTreeMap<String, Vec<Value>>),
+}
+
+impl Dimension {
+    fn expand<P: Palette>(&self, flavors: &[P]) -> Result<(String, Vec<Value>), Error> {
+        let (name, values) = match self {
+            Self::Builtin(name) => {
+                let values: Vec<Value> = match name.as_str() {
+                    "flavor" => flavors.iter().map(|f| f.name().into()).collect(),
+                    "accent" => {
+                        let mut accents: Vec<Value> = vec![];
+                        for accent in flavors.iter().flat_map(Palette::accents) {
+                            if !accents.iter().any(|a| *a == accent) {
+                                accents.push(accent.into());
+                            }
+                        }
+                        accents
+                    }
+                    _ => return Err(Error::UnknownDimension(name.clone())),
+                };
+                (name.clone(), values)
+            }
+            Self::Values(map) => {
+                let mut entries = map.iter();
+                let (Some((name, values)), None) = (entries.next(), entries.next()) else {
+                    return Err(Error::InvalidDimension(map.keys().cloned().collect()));
+                };
+                (name.clone(), values.clone())
+            }
+        };
+
+        if values.is_empty() {
+            return Err(Error::EmptyDimension(name));
+        }
+        Ok((name, values))
+    }
+}
+
+/// Expand the matrix into every combination of its dimensions' values.
+///
+/// Each combination maps dimension names to values. The first dimension
+/// varies slowest. The built-in `flavor` dimension iterates over `flavors`,
+/// and `accent` over their accents.
+///
+/// # Errors
+///
+/// Returns an error if any dimension is unknown, malformed, or empty.
+pub fn combinations<P: Palette>(
+    dimensions: &[Dimension],
+    flavors: &[P],
+) -> Result<Vec<Map<String, Value>>, Error> {
+    let mut result = vec![Map::new()];
+    for dimension in dimensions {
+        let (name, values) = dimension.expand(flavors)?;
+        let mut expanded = Vec::with_capacity(result.len() * values.len());
+        for combination in result {
+            for value in &values {
+                let mut combination = combination.clone();
+                combination.insert(name.clone(), value.clone());
+                expanded.push(combination);
+            }
+        }
+        result = expanded;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(yaml: &str) -> Vec<Dimension> {
+        serde_yaml::from_str(yaml).expect("valid yaml fixture")
+    }
+
+    fn combinations(dimensions: &[Dimension]) -> Result<Vec<Map<String, Value>>, Error> {
+        super::combinations(dimensions, &crate::template::FLAVORS)
+    }
+
+    #[test]
+    fn builtin_dimensions() {
+        let result = combinations(&parse("[flavor, accent]")).expect("valid matrix");
+        assert_eq!(result.len(), 4 * 14);
+        assert_eq!(result[0]["flavor"], "latte");
+        assert_eq!(result[0]["accent"], "rosewater");
+        assert_eq!(result[1]["accent"], "flamingo");
+        assert_eq!(result[14]["flavor"], "frappe");
+    }
+
+    #[test]
+    fn explicit_values() {
+        let result =
+            combinations(&parse("[{accent: [red, blue]}, {size: [1, 2, 3]}]")).expect("valid");
+        assert_eq!(result.len(), 6);
+        assert_eq!(result[5]["accent"], "blue");
+        assert_eq!(result[5]["size"], 3);
+    }
+
+    #[test]
+    fn no_dimensions() {
+        let result = combinations(&[]).expect("valid matrix");
+        assert_eq!(result, vec![Map::new()]);
+    }
+
+    #[test]
+    fn invalid_dimensions() {
+        assert!(matches!(
+            combinations(&parse("[size]")),
+            Err(Error::UnknownDimension(_))
+        ));
+        assert!(matches!(
+            combinations(&parse("[{a: [1], b: [2]}]")),
+            Err(Error::InvalidDimension(_))
+        ));
+        assert!(matches!(
+            combinations(&parse("[{a: []}]")),
+            Err(Error::EmptyDimension(_))
+        ));
+    }
+}
@@ -0,0 +1,391 @@
+use std::path::Path;
+
+use indexmap::IndexMap;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::template::{ACCENTS, FLAVORS};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("failed to read palette file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("unknown palette file format {0:?}, expected json, yaml, or toml")]
+    UnknownFormat(String),
+
+    #[error("failed to parse palette file: {0}")]
+    Parse(String),
+
+    #[error("palette file must have exactly one of `colors` or `flavors`")]
+    InvalidLayout,
+
+    #[error("palette file has no flavors")]
+    NoFlavors,
+
+    #[error("invalid color {value:?} for `{name}` in flavor `{flavor}`, expected a hex color")]
+    InvalidColor {
+        flavor: String,
+        name: String,
+        value: String,
+    },
+
+    #[error("accent `{name}` is not a color in flavor `{flavor}`")]
+    UnknownAccent { flavor: String, name: String },
+
+    #[error("color `{name}` in flavor `{flavor}` has the name of a built-in context variable")]
+    ReservedName { flavor: String, name: String },
+}
+
+/// Keys of the template context that colors are set alongside, so they can't
+/// be used as color names.
+const RESERVED_NAMES: [&str; 10] = [
+    "flavor",
+    "flavors",
+    "palette",
+    "colors",
+    "accents",
+    "neutrals",
+    "isLight",
+    "isDark",
+    "accent",
+    "accentName",
+];
+
+/// A source of colors for the template context, such as one Catppuccin
+/// flavor. Embedders can implement this to render templates with their own
+/// colors.
+pub trait Palette {
+    /// The name of the flavor, available to templates as `flavor`.
+    fn name(&self) -> &str;
+
+    /// Whether the flavor is light, which drives `isLight`, `isDark`, and
+    /// the `darklight` helper.
+    fn is_light(&self) -> bool;
+
+    /// Color names and lowercase hex codes without a leading `#`, in
+    /// palette order.
+    fn colors(&self) -> Vec<(String, String)>;
+
+    /// The names of the colors that are accents rather than neutrals, in
+    /// palette order. By default, there are none.
+    fn accents(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+impl Palette for catppuccin::Flavour {
+    fn name(&self) -> &str {
+        Self::name(*self)
+    }
+
+    fn is_light(&self) -> bool {
+        *self == Self::Latte
+    }
+
+    fn colors(&self) -> Vec<(String, String)> {
+        self.colours()
+            .into_fields_iter()
+            .map(|(name, c)| (name.to_string(), c.hex().to_ascii_lowercase()))
+            .collect()
+    }
+
+    fn accents(&self) -> Vec<String> {
+        ACCENTS.iter().map(ToString::to_string).collect()
+    }
+}
+
+/// One flavor of a palette: a set of named colors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flavor {
+    pub name: String,
+    pub light: bool,
+    /// Color names and lowercase hex codes, in palette order.
+    pub colors: Vec<(String, String)>,
+    /// Names of the colors that are accents.
+    pub accents: Vec<String>,
+}
+
+impl Palette for Flavor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn is_light(&self) -> bool {
+        self.light
+    }
+
+    fn colors(&self) -> Vec<(String, String)> {
+        self.colors.clone()
+    }
+
+    fn accents(&self) -> Vec<String> {
+        self.accents.clone()
+    }
+}
+
+impl From<catppuccin::Flavour> for Flavor {
+    fn from(flavor: catppuccin::Flavour) -> Self {
+        Self {
+            name: Palette::name(&flavor).to_string(),
+            light: flavor.is_light(),
+            colors: Palette::colors(&flavor),
+            accents: flavor.accents(),
+        }
+    }
+}
+
+/// The four Catppuccin flavors.
+#[must_use]
+pub fn catppuccin() -> Vec<Flavor> {
+    FLAVORS.into_iter().map(Flavor::from).collect()
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FlavorFile {
+    #[serde(default)]
+    light: bool,
+    colors: IndexMap<String, String>,
+    accents: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PaletteFile {
+    name: Option<String>,
+    #[serde(default)]
+    light: bool,
+    colors: Option<IndexMap<String, String>>,
+    #[serde(default)]
+    accents: Vec<String>,
+    flavors: Option<IndexMap<String, FlavorFile>>,
+}
+
+fn make_flavor(
+    name: String,
+    light: bool,
+    colors: IndexMap<String, String>,
+    accents: &[String],
+) -> Result<Flavor, Error> {
+    if let Some(color) = colors.keys().find(|c| RESERVED_NAMES.contains(&c.as_str())) {
+        return Err(Error::ReservedName {
+            flavor: name,
+            name: color.clone(),
+        });
+    }
+
+    if let Some(accent) = accents.iter().find(|a| !colors.contains_key(*a)) {
+        return Err(Error::UnknownAccent {
+            flavor: name,
+            name: accent.clone(),
+        });
+    }
+
+    let colors: Vec<(String, String)> = colors
+        .into_iter()
+        .map(|(color, value)| match crate::parse::normalize_hex(&value) {
+            Ok(hex) => Ok((color, hex.to_ascii_lowercase())),
+            Err(_) => Err(Error::InvalidColor {
+                flavor: name.clone(),
+                name: color,
+                value,
+            }),
+        })
+        .collect::<Result<_, _>>()?;
+
+    // keep accents in palette order, whatever order they were listed in
+    let accents = colors
+        .iter()
+        .filter(|(color, _)| accents.contains(color))
+        .map(|(color, _)| color.clone())
+        .collect();
+
+    Ok(Flavor {
+        name,
+        light,
+        colors,
+        accents,
+    })
+}
+
+/// Parse a palette from `source`, in the given format (`json`, `yaml`, or `toml`).
+///
+/// A palette either has a top-level `colors` map for a single flavor, or a
+/// `flavors` map from flavor names to their own `colors`. Flavors can be
+/// marked as light with `light: true`. A single flavor is named by the
+/// top-level `name`, or `default_name` if there is none.
+///
+/// The colors that are accents can be listed in `accents`, either at the top
+/// level or for each flavor.
+///
+/// # Errors
+///
+/// Returns an error if the source can't be parsed, doesn't follow the layout
+/// described above, contains colors that aren't hex codes, or names a color
+/// after a built-in context variable such as `palette`.
+pub fn parse(source: &str, format: &str, default_name: &str) -> Result<Vec<Flavor>, Error> {
+    let file: PaletteFile = match format {
+        "json" => serde_json::from_str(source).map_err(|e| Error::Parse(e.to_string()))?,
+        "yaml" | "yml" => serde_yaml::from_str(source).map_err(|e| Error::Parse(e.to_string()))?,
+        "toml" => toml::from_str(source).map_err(|e| Error::Parse(e.to_string()))?,
+        _ => return Err(Error::UnknownFormat(format.to_string())),
+    };
+
+    let flavors = match (file.colors, file.flavors) {
+        (Some(colors), None) => vec![make_flavor(
+            file.name.unwrap_or_else(|| default_name.to_string()),
+            file.light,
+            colors,
+            &file.accents,
+        )?],
+        (None, Some(flavors)) => flavors
+            .into_iter()
+            .map(|(name, flavor)| {
+                let accents = flavor.accents.as_ref().unwrap_or(&file.accents);
+                make_flavor(name, flavor.light, flavor.colors, accents)
+            })
+            .collect::<Result<_, _>>()?,
+        _ => return Err(Error::InvalidLayout),
+    };
+
+    if flavors.is_empty() {
+        return Err(Error::NoFlavors);
+    }
+    Ok(flavors)
+}
+
+/// Load a palette file, picking the format from its extension. See [`parse`]
+/// for the layout of the file.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read or parsed.
+pub fn load(path: &Path) -> Result<Vec<Flavor>, Error> {
+    let source = std::fs::read_to_string(path)?;
+    let format = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    let default_name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    parse(&source, format, default_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_flavor() {
+        let source = "colors:\n  red: '#FF0000'\n  base: '101010'\n  text: '#fff'\n";
+        let flavors = parse(source, "yaml", "brand").expect("valid palette");
+        assert_eq!(
+            flavors,
+            vec![Flavor {
+                name: "brand".to_string(),
+                light: false,
+                colors: vec![
+                    ("red".to_string(), "ff0000".to_string()),
+                    ("base".to_string(), "101010".to_string()),
+                    ("text".to_string(), "ffffff".to_string()),
+                ],
+                accents: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    fn multiple_flavors() {
+        let source = r#"
+            [flavors.day]
+            light = true
+            colors = { text = "000000", base = "ffffff" }
+
+            [flavors.night]
+            colors = { text = "ffffff", base = "000000" }
+        "#;
+        let flavors = parse(source, "toml", "brand").expect("valid palette");
+        assert_eq!(flavors.len(), 2);
+        assert_eq!(flavors[0].name, "day");
+        assert!(flavors[0].light);
+        assert_eq!(flavors[1].name, "night");
+        assert!(!flavors[1].light);
+        assert_eq!(
+            flavors[1].colors[0],
+            ("text".to_string(), "ffffff".to_string())
+        );
+    }
+
+    #[test]
+    fn accents() {
+        let source = r#"
+            accents = ["blue", "red"]
+
+            [flavors.day]
+            colors = { red = "ff0000", blue = "0000ff", base = "ffffff" }
+
+            [flavors.night]
+            accents = ["red"]
+            colors = { red = "ff0000", blue = "0000ff", base = "000000" }
+        "#;
+        let flavors = parse(source, "toml", "brand").expect("valid palette");
+        assert_eq!(flavors[0].accents, ["red", "blue"]);
+        assert_eq!(flavors[1].accents, ["red"]);
+
+        assert!(matches!(
+            parse(
+                r#"{"colors": {"red": "ff0000"}, "accents": ["blue"]}"#,
+                "json",
+                "x"
+            ),
+            Err(Error::UnknownAccent { .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_palettes() {
+        assert!(matches!(
+            parse(r#"{"colors": {"red": "nope"}}"#, "json", "x"),
+            Err(Error::InvalidColor { .. })
+        ));
+        assert!(matches!(
+            parse(r#"{"name": "x"}"#, "json", "x"),
+            Err(Error::InvalidLayout)
+        ));
+        assert!(matches!(
+            parse(r#"{"flavors": {}}"#, "json", "x"),
+            Err(Error::NoFlavors)
+        ));
+        assert!(matches!(
+            parse("", "ini", "x"),
+            Err(Error::UnknownFormat(_))
+        ));
+    }
+
+    #[test]
+    fn reserved_names() {
+        for name in RESERVED_NAMES {
+            let source = format!(r#"{{"colors": {{"red": "ff0000", "{name}": "000000"}}}}"#);
+            assert!(matches!(
+                parse(&source, "json", "x"),
+                Err(Error::ReservedName { name: reserved, .. }) if reserved == name
+            ));
+        }
+        assert!(parse(r#"{"colors": {"flavour": "000000"}}"#, "json", "x").is_ok());
+    }
+
+    #[test]
+    fn catppuccin_flavors() {
+        let flavors = catppuccin();
+        assert_eq!(flavors.len(), 4);
+        assert_eq!(flavors[3].name, "mocha");
+        assert_eq!(
+            flavors[3].colors[0],
+            ("rosewater".to_string(), "f5e0dc".to_string())
+        );
+        assert!(flavors[0].light);
+    }
+}
@@ -1,20 +1,135 @@
 use std::num::ParseIntError;
 
-use css_colors::{rgba, Color, Ratio, HSLA, RGB, RGBA};
 use thiserror::Error;
 
+use crate::color::Color;
+
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("invalid length {0} for hex string, expected 6 or 8 characters")]
+    #[error("invalid length {0} for hex string, expected 3, 4, 6, or 8 characters")]
     InvalidLength(usize),
 
+    #[error("hex string {0:?} contains characters other than hex digits")]
+    InvalidDigits(String),
+
     #[error("failed to parse as base 16 integer: {0}")]
     ParseInt(ParseIntError),
+
+    #[error("unknown color function `{0}`, expected rgb, rgba, hsl, hsla, hwb, oklab, or oklch")]
+    UnknownFunction(String),
+
+    #[error("invalid color {0:?}")]
+    InvalidFunction(String),
+}
+
+/// Parse a color from a hex string or a CSS color function.
+///
+/// Hex strings can take any of the forms accepted by [`normalize_hex`]. The
+/// color functions are `rgb()`, `rgba()`, `hsl()`, `hsla()`, `hwb()`,
+/// `oklab()`, and `oklch()`, with either comma or space separated arguments.
+///
+/// # Errors
+///
+/// Returns an error if `color` is neither a valid hex string nor a valid
+/// color function.
+pub fn parse_color(color: &str) -> Result<Color, Error> {
+    let color = color.trim();
+    let color = crate::format::unmark(color).unwrap_or(color);
+    let Some((function, args)) = color
+        .strip_suffix(')')
+        .and_then(|color| color.split_once('('))
+    else {
+        return Color::from_hex(color);
+    };
+
+    let invalid = || Error::InvalidFunction(color.to_string());
+    let args = args
+        .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| Number::parse(arg).ok_or_else(invalid))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (&[first, second, third], alpha) = match args.as_slice() {
+        [first, second, third] => (&[*first, *second, *third], None),
+        [first, second, third, alpha] => (&[*first, *second, *third], Some(*alpha)),
+        _ => return Err(invalid()),
+    };
+    let alpha = alpha.map_or(1.0, |alpha| alpha.value(1.0)).clamp(0.0, 1.0);
+
+    let color = match function.trim().to_ascii_lowercase().as_str() {
+        "rgb" | "rgba" => {
+            let [r, g, b] = [first, second, third].map(|channel| channel.value(255.0) / 255.0);
+            Color::new(r, g, b, alpha)
+        }
+        "hsl" | "hsla" => Color::from_hsl(
+            first.degrees(),
+            second.value(100.0) / 100.0,
+            third.value(100.0) / 100.0,
+            alpha,
+        ),
+        "hwb" => hwb(
+            first.degrees(),
+            second.value(100.0) / 100.0,
+            third.value(100.0) / 100.0,
+            alpha,
+        ),
+        "oklab" => Color::from_oklab(first.value(1.0), second.value(0.4), third.value(0.4), alpha),
+        "oklch" => Color::from_oklch(first.value(1.0), second.value(0.4), third.degrees(), alpha),
+        _ => return Err(Error::UnknownFunction(function.trim().to_string())),
+    };
+
+    // out of gamut colors are clipped
+    Ok(color.clip())
+}
+
+/// A number argument to a CSS color function, with its unit if it has one.
+#[derive(Clone, Copy)]
+enum Number {
+    Plain(f64),
+    Percentage(f64),
+    Degrees(f64),
+}
+
+impl Number {
+    fn parse(arg: &str) -> Option<Self> {
+        let units = [
+            ("%", Self::Percentage as fn(f64) -> Self),
+            ("deg", Self::Degrees),
+            ("grad", |n| Self::Degrees(n * 0.9)),
+            ("rad", |n| Self::Degrees(n.to_degrees())),
+            ("turn", |n| Self::Degrees(n * 360.0)),
+        ];
+        for (unit, make) in units {
+            if let Some(number) = arg.strip_suffix(unit) {
+                return number.parse().ok().map(make);
+            }
+        }
+        arg.parse().ok().map(Self::Plain)
+    }
+
+    /// The number, where `100%` is `full`.
+    fn value(self, full: f64) -> f64 {
+        match self {
+            Self::Plain(n) | Self::Degrees(n) => n,
+            Self::Percentage(n) => n / 100.0 * full,
+        }
+    }
+
+    /// The number as an angle in degrees, from 0 to 360.
+    fn degrees(self) -> f64 {
+        let (Self::Plain(n) | Self::Degrees(n) | Self::Percentage(n)) = self;
+        n.rem_euclid(360.0)
+    }
 }
 
-pub trait HSLAExt {
-    fn from_hex(hex: &str) -> Result<HSLA, Error>;
-    fn to_hex(&self) -> String;
+fn hwb(hue: f64, whiteness: f64, blackness: f64, alpha: f64) -> Color {
+    let (whiteness, blackness) = (whiteness.clamp(0.0, 1.0), blackness.clamp(0.0, 1.0));
+    if whiteness + blackness >= 1.0 {
+        let gray = whiteness / (whiteness + blackness);
+        return Color::new(gray, gray, gray, alpha);
+    }
+    let Color { r, g, b, .. } = Color::from_hsl(hue, 1.0, 0.5, alpha);
+    let [r, g, b] = [r, g, b].map(|c| c.mul_add(1.0 - whiteness - blackness, whiteness));
+    Color::new(r, g, b, alpha)
 }
 
 fn hex_to_u8s(hex: &str) -> Result<Vec<u8>, ParseIntError> {
@@ -24,38 +139,133 @@ fn hex_to_u8s(hex: &str) -> Result<Vec<u8>, ParseIntError> {
         .collect()
 }
 
-impl HSLAExt for HSLA {
-    fn from_hex(hex: &str) -> Result<Self, Error> {
-        if hex.len() != 6 && hex.len() != 8 {
-            return Err(Error::InvalidLength(hex.len()));
-        }
+/// Strip the `#` or `0x` prefix from a hex string, and expand 3 and 4 digit
+/// shorthand such as `fff` into 6 and 8 digits.
+///
+/// # Errors
+///
+/// Returns an error if `hex` contains anything other than hex digits after
+/// the prefix, or doesn't have 3, 4, 6, or 8 of them.
+pub fn normalize_hex(hex: &str) -> Result<String, Error> {
+    let digits = hex
+        .strip_prefix('#')
+        .or_else(|| hex.strip_prefix("0x"))
+        .or_else(|| hex.strip_prefix("0X"))
+        .unwrap_or(hex);
 
-        let components = hex_to_u8s(hex).map_err(Error::ParseInt)?;
-        let [red, green, blue]: [u8; 3] = components[..3]
-            .try_into()
-            .expect("guaranteed to have at least 3 elements");
-        let alpha = components.get(3).copied().unwrap_or(255);
-
-        Ok(rgba(red, green, blue, Ratio::from_u8(alpha).as_f32()).to_hsla())
-    }
-
-    fn to_hex(&self) -> String {
-        if self.a.as_u8() == 255 {
-            let RGB { r, g, b } = self.to_rgb();
-            format!("{:02x}{:02x}{:02x}", r.as_u8(), g.as_u8(), b.as_u8())
-        } else {
-            let RGBA { r, g, b, a } = self.to_rgba();
-            format!(
-                "{:02x}{:02x}{:02x}{:02x}",
-                r.as_u8(),
-                g.as_u8(),
-                b.as_u8(),
-                a.as_u8()
-            )
-        }
+    // checked up front, since slicing multi-byte characters would panic and
+    // `u8::from_str_radix` accepts a leading `+`.
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::InvalidDigits(hex.to_string()));
+    }
+
+    match digits.len() {
+        3 | 4 => Ok(digits.chars().flat_map(|c| [c, c]).collect()),
+        6 | 8 => Ok(digits.to_string()),
+        len => Err(Error::InvalidLength(len)),
+    }
+}
+
+/// Parse a hex string into its red, green, blue, and alpha components. See
+/// [`normalize_hex`] for the accepted forms.
+///
+/// # Errors
+///
+/// Returns an error if `hex` isn't a valid hex string.
+pub fn rgba_from_hex(hex: &str) -> Result<[u8; 4], Error> {
+    let hex = normalize_hex(hex)?;
+    let components = hex_to_u8s(&hex).map_err(Error::ParseInt)?;
+    match components[..] {
+        [red, green, blue] => Ok([red, green, blue, 255]),
+        [red, green, blue, alpha] => Ok([red, green, blue, alpha]),
+        _ => Err(Error::InvalidLength(hex.len())),
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes() {
+        let expected = [0xf3, 0x8b, 0xa8, 0xff];
+        for hex in ["f38ba8", "#f38ba8", "0xf38ba8", "0XF38BA8"] {
+            assert_eq!(rgba_from_hex(hex).expect("valid hex"), expected);
+        }
+    }
 
+    #[test]
+    fn shorthand() {
+        assert_eq!(
+            rgba_from_hex("fff").expect("valid hex"),
+            [255, 255, 255, 255]
+        );
+        assert_eq!(
+            rgba_from_hex("#f008").expect("valid hex"),
+            [255, 0, 0, 0x88]
+        );
+        assert_eq!(normalize_hex("#abc").expect("valid hex"), "aabbcc");
+    }
+
+    fn assert_color(color: &str, hex: &str) {
+        assert_eq!(parse_color(color).expect("valid color").to_hex(), hex);
+    }
+
+    #[test]
+    fn css_functions() {
+        assert_color("rgb(243, 139, 168)", "f38ba8");
+        assert_color("rgb(243 139 168)", "f38ba8");
+        assert_color("RGBA(243, 139, 168, 1)", "f38ba8");
+        assert_color("rgb(243 139 168 / 50%)", "f38ba880");
+        assert_color("rgb(100% 0% 0%)", "ff0000");
+        assert_color("hsl(120, 100%, 50%)", "00ff00");
+        assert_color("hsl(0.5turn 100% 50%)", "00ffff");
+        assert_color("hwb(240 0% 0%)", "0000ff");
+        assert_color("hwb(0 60% 60%)", "808080");
+        assert_color("oklch(100% 0 0)", "ffffff");
+        assert_color("oklch(0.628 0.2577 29.23)", "ff0000");
+        assert_color("oklab(62.8% 0.2249 0.1258)", "ff0000");
+        assert_color("oklch(75.6% 0.130 2.8)", "f38ba8");
+    }
+
+    #[test]
+    fn invalid_css_functions() {
+        assert!(matches!(
+            parse_color("rgb(1, 2)"),
+            Err(Error::InvalidFunction(_))
+        ));
+        assert!(matches!(
+            parse_color("rgb(a, b, c)"),
+            Err(Error::InvalidFunction(_))
+        ));
+        assert!(matches!(
+            parse_color("lab(1 2 3)"),
+            Err(Error::UnknownFunction(_))
+        ));
+        assert!(matches!(
+            parse_color("rgb(1, 2, 3"),
+            Err(Error::InvalidDigits(_))
+        ));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            rgba_from_hex("#f38ba"),
+            Err(Error::InvalidLength(5))
+        ));
+        assert!(matches!(
+            rgba_from_hex("zzzzzz"),
+            Err(Error::InvalidDigits(_))
+        ));
+        assert!(matches!(rgba_from_hex("ébc"), Err(Error::InvalidDigits(_))));
+        assert!(matches!(
+            rgba_from_hex("f38ba8é"),
+            Err(Error::InvalidDigits(_))
+        ));
+        assert!(matches!(
+            rgba_from_hex("+1+1+1"),
+            Err(Error::InvalidDigits(_))
+        ));
+    }
+}
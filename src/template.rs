@@ -1,8 +1,9 @@
-use std::collections::HashMap;
-
 use handlebars::{Handlebars, HelperDef};
+use serde_json::json;
 
+use crate::format;
 use crate::helper;
+use crate::palette::Palette;
 
 pub struct Helper {
     pub name: &'static str,
@@ -47,28 +48,122 @@ pub fn helpers() -> Vec<Helper> {
             name: "lighten",
             description: "Lighten a color by a percentage.",
             args: &["color", "amount"],
-            examples: &[("red 0.1", "`f8bacc` / `hsl(343, 81%, 85%)`")],
+            examples: &[("red 0.1", "`f8b9cb` / `hsl(343, 82%, 85%)`")],
             handler: Box::new(helper::lighten),
         },
         Helper {
             name: "darken",
             description: "Darken a color by a percentage.",
             args: &["color", "amount"],
-            examples: &[("red 0.1", "`ee5c85` / `hsl(343, 81%, 65%)`")],
+            examples: &[("red 0.1", "`ee5d85` / `hsl(343, 81%, 65%)`")],
             handler: Box::new(helper::darken),
         },
+        Helper {
+            name: "saturate",
+            description: "Saturate a color by a percentage.",
+            args: &["color", "amount"],
+            examples: &[("red 0.1", "`f985a5` / `hsl(343, 91%, 75%)`")],
+            handler: Box::new(helper::saturate),
+        },
+        Helper {
+            name: "desaturate",
+            description: "Desaturate a color by a percentage.",
+            args: &["color", "amount"],
+            examples: &[("red 0.3", "`e09eb0` / `hsl(344, 52%, 75%)`")],
+            handler: Box::new(helper::desaturate),
+        },
+        Helper {
+            name: "set_lightness",
+            description: "Set the HSL lightness of a color, from 0 to 1.",
+            args: &["color", "lightness"],
+            examples: &[("red 0.5", "`e71852` / `hsl(343, 81%, 50%)`")],
+            handler: Box::new(helper::set_lightness),
+        },
+        Helper {
+            name: "set_hue",
+            description: "Set the HSL hue of a color, in degrees.",
+            args: &["color", "hue"],
+            examples: &[("red 120", "`8bf38b` / `hsl(120, 81%, 75%)`")],
+            handler: Box::new(helper::set_hue),
+        },
+        Helper {
+            name: "complement",
+            description: "Get the complement of a color, with the opposite HSL hue.",
+            args: &["color"],
+            examples: &[("red", "`8bf3d6` / `hsl(163, 81%, 75%)`")],
+            handler: Box::new(helper::complement),
+        },
+        Helper {
+            name: "grayscale",
+            description: "Remove all saturation from a color, keeping its HSL lightness.",
+            args: &["color"],
+            examples: &[("red", "`bfbfbf`")],
+            handler: Box::new(helper::grayscale),
+        },
+        Helper {
+            name: "invert",
+            description: "Invert the red, green, and blue channels of a color.",
+            args: &["color"],
+            examples: &[("red", "`0c7457`")],
+            handler: Box::new(helper::invert),
+        },
         Helper {
             name: "mix",
-            description: "Mix two colors together in a given ratio.",
-            args: &["color_a", "color_b", "ratio"],
-            examples: &[("red base 0.3", "`5e4054` (30% red, 70% base)")],
+            description: "Mix two colors together in a given ratio. The optional `space` is the color space to mix in: `srgb` (the default), `linear`, `oklab`, or `oklch`.",
+            args: &["color_a", "color_b", "ratio", "[space=srgb]"],
+            examples: &[
+                ("red base 0.3", "`5e3f53` (30% red, 70% base)"),
+                ("red base 0.3 space=\"oklab\"", "`583d50`"),
+            ],
             handler: Box::new(helper::mix),
         },
+        Helper {
+            name: "gradient",
+            description: "Make a list of colors evenly spaced from one color to another, including both, for use with `{{#each}}`. The optional `space` is the color space to step through, as for `mix`, and defaults to `oklab`.",
+            args: &["color_a", "color_b", "steps", "[space=oklab]"],
+            examples: &[("base mauve 3", "`1e1e2e`, `6e5e8c`, `cba6f7`")],
+            handler: Box::new(helper::gradient),
+        },
+        Helper {
+            name: "scale",
+            description: "Make a list of shades of a color from light to dark, evenly spaced in OKLCH lightness, for use with `{{#each}}`. 11 steps give a scale like the 50 to 950 shades of Tailwind CSS.",
+            args: &["color", "steps"],
+            examples: &[("mauve 5", "`f8f2ff`, `cda8f9`, `9571be`, `613e86`, `320951`")],
+            handler: Box::new(helper::scale),
+        },
+        Helper {
+            name: "lighten_ok",
+            description: "Lighten a color by an amount of OKLCH lightness, keeping its perceived hue and chroma.",
+            args: &["color", "amount"],
+            examples: &[("red 0.1", "`ffb9ca`")],
+            handler: Box::new(helper::lighten_ok),
+        },
+        Helper {
+            name: "darken_ok",
+            description: "Darken a color by an amount of OKLCH lightness, keeping its perceived hue and chroma.",
+            args: &["color", "amount"],
+            examples: &[("red 0.1", "`d16c89`")],
+            handler: Box::new(helper::darken_ok),
+        },
+        Helper {
+            name: "chroma",
+            description: "Change the OKLCH chroma of a color by an amount. Negative amounts move towards gray.",
+            args: &["color", "amount"],
+            examples: &[("red -0.05", "`db9bab`")],
+            handler: Box::new(helper::chroma),
+        },
+        Helper {
+            name: "hue_rotate",
+            description: "Rotate the OKLCH hue of a color by a number of degrees.",
+            args: &["color", "degrees"],
+            examples: &[("red 180", "`21cab7`")],
+            handler: Box::new(helper::hue_rotate),
+        },
         Helper {
             name: "opacity",
             description: "Set the opacity of a color.",
             args: &["color", "amount"],
-            examples: &[("red 0.5", "`hsla(343, 81%, 75%, 0.50)`")],
+            examples: &[("red 0.5", "`f38ba880`")],
             handler: Box::new(helper::opacity),
         },
         Helper {
@@ -106,6 +201,44 @@ pub fn helpers() -> Vec<Helper> {
             examples: &[("(opacity red 0.6)", "`hsla(343, 81%, 75%, 0.60)`")],
             handler: Box::new(helper::hsla),
         },
+        Helper {
+            name: "oklab",
+            description: "Convert a color to CSS Oklab format.",
+            args: &["color"],
+            examples: &[("red", "`oklab(75.6% 0.130 0.006)`")],
+            handler: Box::new(helper::oklab),
+        },
+        Helper {
+            name: "oklch",
+            description: "Convert a color to CSS OKLCH format.",
+            args: &["color"],
+            examples: &[("red", "`oklch(75.6% 0.130 2.8)`")],
+            handler: Box::new(helper::oklch),
+        },
+        Helper {
+            name: "contrast",
+            description: "Get the WCAG 2 contrast ratio between two colors, from 1 to 21. A translucent first color is drawn over the second.",
+            args: &["color_a", "color_b"],
+            examples: &[("text base", "`11.34` (truncated to 2 places)")],
+            handler: Box::new(helper::contrast),
+        },
+        Helper {
+            name: "apca",
+            description: "Get the APCA lightness contrast (Lc) of text on a background. Positive for dark text on a light background, negative for light text on a dark background.",
+            args: &["text", "background"],
+            examples: &[("text base", "`-79.97` (truncated to 2 places)")],
+            handler: Box::new(helper::apca),
+        },
+        Helper {
+            name: "readable_on",
+            description: "Pick the first candidate color with at least a WCAG 2 contrast ratio of `min` on a background, or the candidate with the most contrast if none do.",
+            args: &["background", "candidates…", "[min=4.5]"],
+            examples: &[
+                ("base overlay0 overlay2 subtext0", "`9399b2` (overlay2, the first with a ratio of at least 4.5)"),
+                ("base overlay0 overlay2 subtext0 min=7", "`a6adc8` (subtext0)"),
+            ],
+            handler: Box::new(helper::readable_on),
+        },
         Helper {
             name: "red_i",
             description: "Get the red channel of a color as an integer from 0 to 255.",
@@ -164,7 +297,7 @@ pub fn helpers() -> Vec<Helper> {
         },
         Helper {
             name: "darklight",
-            description: "Choose a value depending on the current flavor. Latte is light, while Frappé, Macchiato, and Mocha are all dark.",
+            description: "Choose a value depending on whether the current flavor, or the flavor of the current `{{#each flavors}}` iteration, is light or dark. Latte is light, while Frappé, Macchiato, and Mocha are all dark.",
             args: &["if-dark", "if-light"],
             examples: &[("\"Night\" \"Day\"", "`Day` on Latte, `Night` on other flavors")],
             handler: Box::new(helper::darklight),
@@ -178,26 +311,396 @@ pub fn make_registry() -> Handlebars<'static> {
     for helper in helpers() {
         reg.register_helper(helper.name, helper.handler);
     }
+    reg.register_helper("eq", Box::new(helper::eq));
+    reg.register_helper("ne", Box::new(helper::ne));
     reg.set_strict_mode(true);
     reg
 }
 
-#[must_use]
-#[allow(clippy::missing_panics_doc)] // panic here implies an internal issue
-pub fn make_context(flavor: catppuccin::Flavour) -> serde_json::Value {
-    let colors = flavor.colours();
+pub const FLAVORS: [catppuccin::Flavour; 4] = [
+    catppuccin::Flavour::Latte,
+    catppuccin::Flavour::Frappe,
+    catppuccin::Flavour::Macchiato,
+    catppuccin::Flavour::Mocha,
+];
+
+pub const ACCENTS: [&str; 14] = [
+    "rosewater",
+    "flamingo",
+    "pink",
+    "mauve",
+    "red",
+    "maroon",
+    "peach",
+    "yellow",
+    "green",
+    "teal",
+    "sky",
+    "sapphire",
+    "blue",
+    "lavender",
+];
+
+/// Describe a color as an object with its name, hex code, RGB and HSL
+/// components, and whether it is an accent.
+fn make_color_object(name: &str, hex: &str, accent: bool) -> serde_json::Value {
+    let mut color = json!({ "name": name, "hex": hex, "accent": accent });
+    if let Ok(parsed) = crate::parse::parse_color(hex) {
+        let [red, green, blue, _] = parsed.to_rgba8();
+        let (hue, saturation, lightness) = parsed.to_hsl();
+        // rounded to whole degrees and percentages, none of which are negative
+        #[allow(clippy::cast_sign_loss)]
+        let [h, s, l] = [hue, saturation * 100.0, lightness * 100.0].map(|n| n.round() as u16);
+        color["rgb"] = json!({ "r": red, "g": green, "b": blue });
+        color["hsl"] = json!({ "h": h % 360, "s": s, "l": l });
+    }
+    color
+}
 
-    let color_map: HashMap<String, String> = colors
-        .into_fields_iter()
-        .map(|(name, c)| (name.to_string(), c.hex().to_ascii_lowercase()))
+fn make_flavor_context<P: Palette>(flavor: &P) -> serde_json::Value {
+    let colors = flavor.colors();
+    let accents = flavor.accents();
+    let mut context: serde_json::Map<String, serde_json::Value> = colors
+        .iter()
+        .map(|(name, hex)| (name.clone(), hex.clone().into()))
         .collect();
 
-    let mut context =
-        serde_json::to_value(color_map).expect("color names & hexcodes can be serialized");
+    let objects: serde_json::Map<String, serde_json::Value> = colors
+        .iter()
+        .map(|(name, hex)| {
+            let object = make_color_object(name, hex, accents.contains(name));
+            (name.clone(), object)
+        })
+        .collect();
+    let (accent_objects, neutral_objects): (Vec<_>, Vec<_>) = objects
+        .values()
+        .cloned()
+        .partition(|color| color["accent"] == true);
+    context.insert("palette".to_string(), objects.values().cloned().collect());
+    context.insert("accents".to_string(), accent_objects.into());
+    context.insert("neutrals".to_string(), neutral_objects.into());
+    context.insert("colors".to_string(), objects.into());
+    context.insert("flavor".to_string(), flavor.name().into());
+    context.insert("isLight".to_string(), flavor.is_light().into());
+    context.insert("isDark".to_string(), (!flavor.is_light()).into());
 
-    context["flavor"] = flavor.name().into();
-    context["isLight"] = (flavor == catppuccin::Flavour::Latte).into();
-    context["isDark"] = (flavor != catppuccin::Flavour::Latte).into();
+    context.into()
+}
+
+/// Build the template context for the given flavor of the palette.
+///
+/// Colors are available by name as hex codes, and as objects describing
+/// them (`{name, hex, rgb, hsl, accent}`) both in the `colors` map and in
+/// the `palette` list, in palette order for use with `{{#each}}`. The
+/// `accents` and `neutrals` lists split the palette into the accent colors
+/// and the rest.
+///
+/// Every flavor is also made available under the `flavors` key, for example
+/// `flavors.latte.red`. Passing `None` produces a context containing only
+/// the `flavors` map, for templates that render all flavors at once.
+///
+/// Any [`Palette`] can be used, such as [`catppuccin::Flavour`] or a
+/// [`Flavor`](crate::palette::Flavor) loaded from a palette file.
+#[must_use]
+pub fn make_context<P: Palette>(flavors: &[P], flavor: Option<&P>) -> serde_json::Value {
+    let mut context = flavor.map_or_else(
+        || serde_json::Value::Object(serde_json::Map::new()),
+        make_flavor_context,
+    );
+
+    context["flavors"] = flavors
+        .iter()
+        .map(|f| (f.name().to_string(), make_flavor_context(f)))
+        .collect::<serde_json::Map<_, _>>()
+        .into();
 
     context
 }
+
+/// Rebuild the object describing the color `name` in a flavor's context from
+/// the color's current value, such as after an override changed it.
+///
+/// Its entries in `palette`, `accents`, and `neutrals` are replaced too. Does
+/// nothing if `name` isn't a color of the flavor.
+pub fn update_color(flavor: &mut serde_json::Value, name: &str) {
+    let Some(accent) = flavor
+        .get("colors")
+        .and_then(|colors| colors.get(name))
+        .map(|color| color["accent"] == true)
+    else {
+        return;
+    };
+    let Some(hex) = flavor.get(name).and_then(serde_json::Value::as_str) else {
+        return;
+    };
+
+    let object = make_color_object(name, hex, accent);
+    for list in ["palette", "accents", "neutrals"] {
+        if let Some(colors) = flavor
+            .get_mut(list)
+            .and_then(serde_json::Value::as_array_mut)
+        {
+            for color in colors.iter_mut().filter(|color| color["name"] == name) {
+                color.clone_from(&object);
+            }
+        }
+    }
+    flavor["colors"][name] = object;
+}
+
+/// Mark every color in a context made by [`make_context`] with
+/// [`format::mark`], so that they are written in the chosen
+/// [`ColorFormat`](crate::format::ColorFormat) once rendered.
+pub fn mark_colors(ctx: &mut serde_json::Value) {
+    mark_flavor_colors(ctx);
+    if let Some(flavors) = ctx
+        .get_mut("flavors")
+        .and_then(serde_json::Value::as_object_mut)
+    {
+        flavors.values_mut().for_each(mark_flavor_colors);
+    }
+}
+
+fn mark_flavor_colors(flavor: &mut serde_json::Value) {
+    let Some(names) = flavor
+        .get("colors")
+        .and_then(serde_json::Value::as_object)
+        .map(|colors| colors.keys().cloned().collect::<Vec<_>>())
+    else {
+        return;
+    };
+
+    let mark = |value: Option<&mut serde_json::Value>| {
+        if let Some(value) = value {
+            if let Some(hex) = value.as_str() {
+                *value = format::mark(hex).into();
+            }
+        }
+    };
+    for name in &names {
+        mark(flavor.get_mut(name));
+        mark(flavor["colors"][name].get_mut("hex"));
+    }
+    for list in ["palette", "accents", "neutrals"] {
+        if let Some(colors) = flavor
+            .get_mut(list)
+            .and_then(serde_json::Value::as_array_mut)
+        {
+            for color in colors {
+                mark(color.get_mut("hex"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_flavor_context() {
+        let flavors = crate::palette::catppuccin();
+        let ctx = make_context(&flavors, Some(&flavors[3]));
+        assert_eq!(ctx["flavor"], "mocha");
+        assert_eq!(ctx["base"], "1e1e2e");
+        assert_eq!(ctx["flavors"]["latte"]["base"], "eff1f5");
+    }
+
+    #[test]
+    fn all_flavors_context() {
+        let flavors = crate::palette::catppuccin();
+        let ctx = make_context(&flavors, None);
+        assert!(ctx.get("flavor").is_none());
+        assert!(ctx.get("base").is_none());
+        for flavor in FLAVORS {
+            assert_eq!(ctx["flavors"][flavor.name()]["flavor"], flavor.name());
+        }
+    }
+
+    #[test]
+    fn palette_order() {
+        let ctx = make_context(&FLAVORS, Some(&FLAVORS[3]));
+        let palette = ctx["palette"].as_array().expect("palette is a list");
+        assert_eq!(palette.len(), 26);
+        assert_eq!(palette[0]["name"], "rosewater");
+        assert_eq!(palette[0]["hex"], "f5e0dc");
+        assert_eq!(palette[25]["name"], "crust");
+
+        let keys: Vec<&String> = ctx
+            .as_object()
+            .expect("context is an object")
+            .keys()
+            .collect();
+        assert_eq!(keys[..3], ["rosewater", "flamingo", "pink"]);
+
+        let flavors: Vec<&String> = ctx["flavors"]
+            .as_object()
+            .expect("flavors is a map")
+            .keys()
+            .collect();
+        assert_eq!(flavors, ["latte", "frappe", "macchiato", "mocha"]);
+    }
+
+    #[test]
+    fn color_objects() {
+        let ctx = make_context(&FLAVORS, Some(&FLAVORS[3]));
+        let red = &ctx["colors"]["red"];
+        assert_eq!(red["name"], "red");
+        assert_eq!(red["hex"], "f38ba8");
+        assert_eq!(red["accent"], true);
+        assert_eq!(red["rgb"], json!({ "r": 243, "g": 139, "b": 168 }));
+        assert_eq!(red["hsl"], json!({ "h": 343, "s": 81, "l": 75 }));
+        assert_eq!(ctx["colors"]["base"]["accent"], false);
+        assert_eq!(&ctx["palette"][4], red);
+    }
+
+    #[test]
+    fn accents_and_neutrals() {
+        let ctx = make_context(&FLAVORS, Some(&FLAVORS[0]));
+        let names = |key: &str| -> Vec<String> {
+            ctx[key]
+                .as_array()
+                .expect("list of colors")
+                .iter()
+                .map(|c| c["name"].as_str().expect("color name").to_string())
+                .collect()
+        };
+        assert_eq!(names("accents"), ACCENTS);
+        assert_eq!(names("neutrals")[0], "text");
+        assert_eq!(names("neutrals").last().map(String::as_str), Some("crust"));
+        assert_eq!(ctx["accents"][0]["hex"], "dc8a78");
+    }
+
+    #[test]
+    fn custom_palette_context() {
+        struct Mono;
+
+        impl Palette for Mono {
+            fn name(&self) -> &'static str {
+                "mono"
+            }
+
+            fn is_light(&self) -> bool {
+                true
+            }
+
+            fn colors(&self) -> Vec<(String, String)> {
+                vec![("ink".to_string(), "000000".to_string())]
+            }
+        }
+
+        let ctx = make_context(&[Mono], Some(&Mono));
+        assert_eq!(ctx["flavor"], "mono");
+        assert_eq!(ctx["ink"], "000000");
+        assert_eq!(ctx["isLight"], true);
+        assert_eq!(ctx["isDark"], false);
+        assert_eq!(ctx["flavors"]["mono"]["ink"], "000000");
+
+        let catppuccin = crate::palette::catppuccin();
+        assert_eq!(
+            make_context(&FLAVORS, Some(&FLAVORS[1])),
+            make_context(&catppuccin, Some(&catppuccin[1]))
+        );
+    }
+
+    #[test]
+    fn helper_arguments() {
+        let reg = make_registry();
+        let ctx = make_context(&FLAVORS, Some(&FLAVORS[3]));
+        let render = |template: &str| reg.render_template(template, &ctx).expect("renders");
+
+        assert_eq!(
+            render("{{readable_on base overlay0 overlay2 subtext0}}"),
+            "9399b2"
+        );
+        assert_eq!(
+            render("{{readable_on base overlay0 overlay2 subtext0 min=7}}"),
+            "a6adc8"
+        );
+        assert_eq!(render("{{readable_on base overlay0 min=21}}"), "6c7086");
+        assert_eq!(render("{{mix red base 0.3 space=\"oklab\"}}"), "583d50");
+        assert_eq!(
+            render("{{#each (gradient base mauve 3)}}{{this}} {{/each}}"),
+            "1e1e2e 6e5e8c cba6f7 "
+        );
+        assert_eq!(
+            render("{{#each (scale mauve 2)}}{{this}} {{/each}}"),
+            "f8f2ff 320951 "
+        );
+        assert!(reg.render_template("{{readable_on base}}", &ctx).is_err());
+        assert!(reg
+            .render_template("{{mix red base 0.3 space=\"cmyk\"}}", &ctx)
+            .is_err());
+    }
+
+    #[test]
+    fn color_names() {
+        let reg = make_registry();
+        let ctx = make_context(&FLAVORS, Some(&FLAVORS[3]));
+        let render = |template: &str| reg.render_template(template, &ctx);
+
+        assert_eq!(render("{{darken \"red\" 0}}").expect("renders"), "f38ba8");
+        assert_eq!(
+            render("{{mix \"red\" \"base\" 0.5}}").expect("renders"),
+            "89556b"
+        );
+
+        let all = make_context(&FLAVORS, None);
+        assert_eq!(
+            reg.render_template("{{#each flavors}}{{darken \"red\" 0}} {{/each}}", &all)
+                .expect("renders"),
+            "d20f39 e78284 ed8796 f38ba8 "
+        );
+
+        let error = render("{{lighten \"f38ba\" 0.1}}").expect_err("invalid color");
+        assert!(error.desc.contains("invalid length 5"), "{}", error.desc);
+    }
+
+    #[test]
+    fn marked_colors_are_plain_to_templates() {
+        let reg = make_registry();
+        let mut ctx = make_context(&FLAVORS, Some(&FLAVORS[3]));
+        mark_colors(&mut ctx);
+        let render = |template: &str| {
+            let rendered = reg.render_template(template, &ctx).expect("renders");
+            format::ColorFormat::Hash.apply(&rendered)
+        };
+
+        assert_eq!(render("{{#if (eq red \"f38ba8\")}}yes{{/if}}"), "yes");
+        assert_eq!(render("{{#if (ne red \"f38ba8\")}}yes{{/if}}"), "");
+        assert_eq!(render("{{#if (eq red colors.red.hex)}}yes{{/if}}"), "yes");
+        assert_eq!(render("{{#if (eq flavor \"mocha\")}}yes{{/if}}"), "yes");
+        assert_eq!(
+            render("{{uppercase red}} {{lowercase red}}"),
+            "F38BA8 f38ba8"
+        );
+        assert_eq!(render("{{red}} {{darken red 0}}"), "#f38ba8 #f38ba8");
+    }
+
+    #[test]
+    fn darklight() {
+        let reg = make_registry();
+        let render = |template: &str, ctx: &serde_json::Value| {
+            reg.render_template(template, ctx).expect("renders")
+        };
+
+        let latte = make_context(&FLAVORS, Some(&FLAVORS[0]));
+        assert_eq!(render("{{darklight \"dark\" \"light\"}}", &latte), "light");
+        assert_eq!(
+            render(
+                "{{#each palette}}{{#if @first}}{{darklight \"dark\" \"light\"}}{{/if}}{{/each}}",
+                &latte
+            ),
+            "light"
+        );
+
+        let all = make_context(&FLAVORS, None);
+        assert_eq!(
+            render(
+                "{{#each flavors}}{{@key}}={{darklight \"dark\" \"light\"}} {{/each}}",
+                &all
+            ),
+            "latte=light frappe=dark macchiato=dark mocha=dark "
+        );
+    }
+}